@@ -1,4 +1,5 @@
 use anyhow::{Ok, Result};
+#[cfg(test)]
 use assert_fs::fixture::FileWriteStr;
 use queues::{queue, IsQueue, Queue};
 use std::cmp::max;
@@ -446,7 +447,6 @@ fn dirs_content_is_equivalent_to_itself() -> Result<()> {
     dir1_file1.write_str("FILE1_CONTENT")?;
     let dir1_file2 = assert_fs::NamedTempFile::new(dir1.as_ref().join("file2.txt"))?;
     dir1_file2.write_str("FILE2_CONTENT")?;
-    let dir2 = assert_fs::TempDir::new()?;
 
     let result = compare_dirs_content(dir1.as_ref(), dir1.as_ref())?;
 
@@ -0,0 +1,62 @@
+//! Plugin writing a properties file per locale, plurals as `key.quantity` entries and
+//! every section starting with a `# [[name]]` comment.
+//!
+//! Used by the plugin tests, e.g.
+//! `utas props strings res en --plugin "props=target/debug/examples/props_plugin"`
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let request: Value = serde_json::from_str(&input)?;
+    assert_eq!(request["version"], 1);
+
+    let mut files: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for source in request["sources"].as_array().into_iter().flatten() {
+        for section in source["file"]["sections"].as_array().into_iter().flatten() {
+            let mut started: Vec<String> = Vec::new();
+            for key in section["keys"].as_array().into_iter().flatten() {
+                for string in key["localizations"].as_array().into_iter().flatten() {
+                    let path = format!(
+                        "{}/{}.properties",
+                        text(&string["language_code"]),
+                        text(&source["name"])
+                    );
+                    let lines = files.entry(path.clone()).or_default();
+                    if !started.contains(&path) {
+                        if let Some(name) = section["name"].as_str() {
+                            lines.push(format!("# [[{}]]", name));
+                        }
+                        started.push(path);
+                    }
+                    let value = &string["value"];
+                    if let Some(single) = value.get("single") {
+                        lines.push(format!("{} = {}", text(&key["name"]), text(single)));
+                    }
+                    let quantities = value["plural"]["quantities"].as_array();
+                    for plural in quantities.into_iter().flatten() {
+                        lines.push(format!(
+                            "{}.{} = {}",
+                            text(&key["name"]),
+                            text(&plural["quantity"]),
+                            text(&plural["text"])
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    let response: Vec<Value> = files
+        .into_iter()
+        .map(|(path, lines)| json!({ "path": path, "contents": lines.join("\n") + "\n" }))
+        .collect();
+    serde_json::to_writer(io::stdout(), &response)?;
+    Ok(())
+}
+
+fn text(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
@@ -1,150 +1,147 @@
-use anyhow::{anyhow, Ok, Result};
+use anyhow::{Ok, Result};
 use lazy_static::lazy_static;
-use regex::{Captures, Match, Regex};
-use std::{collections::HashMap, io::Write, path::Path};
-use std::borrow::Cow;
-use std::fs;
+use rayon::prelude::*;
+use regex::{Captures, Regex};
+use std::path::Path;
+
+use crate::generator::{GenResult, Generator, Line, StrLines};
+use crate::output::OutputFiles;
+use crate::parse::{File, PluralValue, StringValue};
+use crate::pipeline::{GenOptions, Source};
+#[cfg(test)]
+use crate::generator::Locale;
+#[cfg(test)]
+use crate::parse::{Key, LocalizedString, Section};
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+/// Writes `values-<locale>/<source name>.xml` for every source
+pub struct AndroidGenerator;
+
+impl Generator for AndroidGenerator {
+    fn name(&self) -> &str {
+        "android"
+    }
 
-use crate::parse::{File, Key, LocalizedString, PluralValue, Section, StringValue};
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let rendered = sources
+            .par_iter()
+            .map(|source| {
+                let generated = generate(&source.file)?;
+                Ok(render(
+                    &generated,
+                    &options.output_dir,
+                    &source.name,
+                    &options.default_lang,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(rendered.into_iter().flatten().collect())
+    }
 
-#[derive(PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Clone)]
-pub struct Locale {
-    value: String,
+    fn file_per_source(&self) -> bool {
+        true
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone)]
-pub struct StrLines {
-    value: Vec<Line>,
+fn format_line(line: &Line, convert: &dyn Fn(&str) -> String) -> Vec<String> {
+    match line.value {
+        StringValue::Single(text) => vec![generate_str_value(line.name, &convert(text))],
+        StringValue::Plural { quantities } => {
+            let converted: Vec<PluralValue> = quantities
+                .iter()
+                .map(|item| PluralValue {
+                    quantity: item.quantity.clone(),
+                    text: convert(&item.text),
+                })
+                .collect();
+            generate_plural_value(line.name, &converted)
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone)]
-pub struct Line {
-    name: String,
-    value: StringValue,
+/// Renders `values-<locale>/<file_name>.xml` files, the default locale is also rendered into `values`
+pub fn render(
+    generated: &GenResult,
+    dir: &Path,
+    file_name: &str,
+    default_lang: &Option<String>,
+) -> OutputFiles {
+    // Locales are rendered concurrently but collected in their order,
+    // so locales sharing a directory (see update_special_locales) always resolve the same way
+    let rendered: Vec<_> = generated
+        .value
+        .par_iter()
+        .map(|(locale, lines)| {
+            let file = format!("{}.xml", file_name);
+            let contents = render_resources(lines);
+
+            let mut files = Vec::with_capacity(2);
+            if default_lang.as_ref() == Some(&locale.value) {
+                files.push((dir.join("values").join(&file), contents.clone()));
+            }
+            files.push((dir.join(values_dir_name(&locale.value)).join(&file), contents));
+            files
+        })
+        .collect();
+    rendered.into_iter().flatten().collect()
 }
 
-impl Line {
-    fn format(&self) -> Vec<String> {
-        match &self.value {
-            StringValue::Single(text) => vec![generate_str_value(&self.name, text)],
-            StringValue::Plural { quantities } => generate_plural_value(&self.name, quantities),
-        }
+/// Name of the resources directory for a twine locale, e.g. "values-en-rGB" for "en-GB"
+pub fn values_dir_name(locale: &str) -> String {
+    lazy_static! {
+        static ref LANG_WITH_REGION_RE: Regex = Regex::new(r"-(\p{Lu})").unwrap();
     }
+    let lang = LANG_WITH_REGION_RE.replace_all(locale, |caps: &Captures| {
+        format!("-r{}", caps.get(1).unwrap().as_str())
+    });
+    format!("values-{}", update_special_locales(&lang))
 }
 
-pub struct GenResult {
-    value: HashMap<Locale, StrLines>,
+/// Renders contents of a resources file with all `lines`
+pub fn render_resources(lines: &StrLines) -> String {
+    render_resources_with(lines, &|text| text.to_string())
 }
 
-impl GenResult {
-    pub fn write(
-        &self,
-        dir: impl AsRef<Path>,
-        file_name: &str,
-        default_lang: &Option<String>,
-    ) -> Result<()> {
-        lazy_static! {
-            static ref LANG_WITH_REGION_RE: Regex = Regex::new(r"-(\p{Lu})").unwrap();
+/// Renders contents of a resources file with all `lines`, texts are passed through `convert`
+pub fn render_resources_with(lines: &StrLines, convert: &dyn Fn(&str) -> String) -> String {
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    result.push('\n');
+    result.push_str("<resources>\n");
+    for line in &lines.value {
+        for item in format_line(line, convert) {
+            result.push_str(&format!("  {}\n", item));
         }
-        for (locale, lines) in &self.value {
-            let lang = LANG_WITH_REGION_RE.replace_all(&locale.value, |caps: &Captures| {
-                format!("-r{}", caps.get(1).unwrap().as_str())
-            });
-            let lang = update_special_locales(&lang);
-
-            let subpath = dir.as_ref().join(format!("values-{}", lang));
-            if !subpath.is_dir() {
-                fs::create_dir(&subpath)?;
-            }
-            let filepath = subpath.join(format!("{}.xml", file_name));
-            let mut file = fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(&filepath)?;
-            file.write("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".as_bytes())?;
-            file.write("\n".as_bytes())?;
-            file.write("<resources>\n".as_bytes())?;
-            for line in &lines.value {
-                let formatted = line.format();
-                for item in formatted {
-                    file.write(format!("  {}\n", item).as_bytes())?;
-                }
-            }
-            file.write("</resources>\n".as_bytes())?;
-            match default_lang {
-                Some(lang) => {
-                    if lang == &locale.value {
-                        let subpath = dir.as_ref().join("values");
-                        if !subpath.is_dir() {
-                            fs::create_dir(&subpath)?;
-                        }
-                        let copy = subpath.join(format!("{}.xml", file_name));
-                        fs::copy(filepath, copy)?;
-                    }
-                }
-                None => (),
-            }
-        }
-        Ok(())
     }
+    result.push_str("</resources>\n");
+    result
 }
 
 // https://stackoverflow.com/questions/17275697/is-there-any-need-to-prepare-values-zh-and-values-zh-rhk/17276279
 fn update_special_locales(code: &str) -> String {
-    return match code {
+    match code {
         "zh-rHans" | "zh-rHant" | "zh-rPinyin" => {
             "zh-rCN".to_string()
         }
         &_ => {
             code.to_string()
         }
-    };
-}
-
-pub fn generate(source: &File) -> Result<GenResult> {
-    if source.sections.len() > 1 {
-        panic!("Expected only one section currently")
-    };
-
-    let Some(keys) = source.sections.first().map(|section| &section.keys) else {
-        return Err(anyhow!("Expected at least one section"))
-    };
-
-    let mut result: HashMap<Locale, StrLines> = HashMap::new();
-    let keys_len = keys.len();
-    for key in keys {
-        let str_name = &key.name;
-        for str in &key.localizations {
-            let code = Locale {
-                value: str.language_code.clone(),
-            };
-
-            let current = &mut result
-                .entry(code)
-                .or_insert(StrLines {
-                    value: Vec::with_capacity(keys_len),
-                })
-                .value;
-
-            current.push(Line {
-                name: str_name.clone(),
-                value: str.value.clone(),
-            })
-        }
     }
+}
 
-    Ok(GenResult { value: result })
+pub fn generate(source: &File) -> Result<GenResult<'_>> {
+    GenResult::from_files([source])
 }
 
 fn generate_str_value(str_name: &str, str_value: &str) -> String {
-    String::from(format!(
+    format!(
         "<string name=\"{}\">{}</string>",
         str_name, str_value
-    ))
+    )
 }
 
-fn generate_plural_value(str_name: &String, items: &Vec<PluralValue>) -> Vec<String> {
+fn generate_plural_value(str_name: &str, items: &[PluralValue]) -> Vec<String> {
     let mut result: Vec<String> = Vec::with_capacity(items.len() + 2);
     result.push(format!("<plurals name=\"{}\">", str_name));
 
@@ -159,6 +156,7 @@ fn generate_plural_value(str_name: &String, items: &Vec<PluralValue>) -> Vec<Str
 }
 
 // -----------------------------  test tools ------------------------------
+#[cfg(test)]
 fn plain_str(lang: &str, txt: &str) -> LocalizedString {
     LocalizedString {
         language_code: lang.to_string(),
@@ -166,6 +164,7 @@ fn plain_str(lang: &str, txt: &str) -> LocalizedString {
     }
 }
 
+#[cfg(test)]
 fn plurals(lang: &str, quantities: Vec<PluralValue>) -> LocalizedString {
     LocalizedString {
         language_code: lang.to_string(),
@@ -173,6 +172,7 @@ fn plurals(lang: &str, quantities: Vec<PluralValue>) -> LocalizedString {
     }
 }
 
+#[cfg(test)]
 fn plural_val(quantity: &str, text: &str) -> PluralValue {
     PluralValue {
         quantity: quantity.to_string(),
@@ -180,35 +180,35 @@ fn plural_val(quantity: &str, text: &str) -> PluralValue {
     }
 }
 
+#[cfg(test)]
 fn key(name: &str, localizations: Vec<LocalizedString>) -> Key {
     Key {
         name: name.to_string(),
-        localizations: localizations,
+        comment: None,
+        tags: Vec::new(),
+        localizations,
     }
 }
 
-fn sorted_strings(input: GenResult) -> Vec<(Locale, StrLines)> {
-    let mut result = Vec::with_capacity(input.value.len());
-    let mut keys: Vec<&Locale> = input.value.keys().collect();
-    keys.sort();
-    for key in keys {
-        result.push((key.clone(), input.value.get(&key).unwrap().clone()))
+// Expected lines borrow values just like generated ones do, so the values are leaked
+#[cfg(test)]
+fn single(name: &'static str, text: &str) -> Line<'static> {
+    Line {
+        name,
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Single(text.to_string()))),
     }
-    result
-}
-
-fn single(name: &str, text: &str) -> Line {
-    return Line {
-        name: name.to_string(),
-        value: StringValue::Single(text.to_string()),
-    };
 }
 
-fn plural(name: &str, items: Vec<PluralValue>) -> Line {
-    return Line {
-        name: name.to_string(),
-        value: StringValue::Plural { quantities: items },
-    };
+#[cfg(test)]
+fn plural(name: &'static str, items: Vec<PluralValue>) -> Line<'static> {
+    Line {
+        name,
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Plural { quantities: items })),
+    }
 }
 
 // ------------------------------- tests -----------------------------------
@@ -217,9 +217,9 @@ fn generate_1_lang_1_str() -> Result<()> {
     let localizations_kek = vec![plain_str("ru", "Кек")];
     let keys = vec![key("kek", localizations_kek)];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "ru".to_string(),
         },
@@ -231,7 +231,7 @@ fn generate_1_lang_1_str() -> Result<()> {
     let expected = GenResult { value: map };
 
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -244,9 +244,9 @@ fn generate_1_lang_2_str() -> Result<()> {
     let keys = vec![key("kek", localizations_kek), key("lil", localizations_lil)];
 
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "ru".to_string(),
         },
@@ -258,7 +258,7 @@ fn generate_1_lang_2_str() -> Result<()> {
     let expected = GenResult { value: map };
 
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -274,17 +274,21 @@ fn generate_3_lang_2_str() -> Result<()> {
     let keys = vec![
         Key {
             name: "find".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_find,
         },
         Key {
             name: "search".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_search,
         },
     ];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([
+    let map = BTreeMap::from([
         (
             Locale {
                 value: "ru".to_string(),
@@ -314,7 +318,7 @@ fn generate_3_lang_2_str() -> Result<()> {
     let expected = GenResult { value: map };
 
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -327,9 +331,9 @@ fn generate_1_lang_1_str_2_placeholders() -> Result<()> {
     }];
     let keys = vec![key("add", localizations_add)];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "mn".to_string(),
         },
@@ -341,7 +345,7 @@ fn generate_1_lang_1_str_2_placeholders() -> Result<()> {
     let expected = GenResult { value: map };
 
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -361,12 +365,14 @@ fn generate_1_lang_1_simple_plural() -> Result<()> {
     let localizations_songs = vec![plurals("mn", vec![plural_val("other", "%d дуу")])];
     let keys = vec![Key {
         name: "songs".to_string(),
+        comment: None,
+        tags: Vec::new(),
         localizations: localizations_songs,
     }];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "mn".to_string(),
         },
@@ -382,7 +388,7 @@ fn generate_1_lang_1_simple_plural() -> Result<()> {
     )]);
     let expected = GenResult { value: map };
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
     Ok(())
 }
 
@@ -400,17 +406,21 @@ fn generate_1_lang_1_str_1_plurals() -> Result<()> {
     let keys = vec![
         Key {
             name: "chicken".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_chicken,
         },
         Key {
             name: "cows".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_cows,
         },
     ];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "en".to_string(),
         },
@@ -440,7 +450,7 @@ fn generate_1_lang_1_str_1_plurals() -> Result<()> {
     let expected = GenResult { value: map };
 
     let actual = generate(&source)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    assert_eq!(expected, actual);
 
     Ok(())
 }
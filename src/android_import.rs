@@ -0,0 +1,328 @@
+//! Reads Android resource directories with `values*/strings.xml` into the parsed model,
+//! so existing Android strings can be moved to twine files.
+
+use anyhow::{anyhow, Ok, Result};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use roxmltree::{Document, Node, NodeType};
+use std::fs;
+use std::path::Path;
+
+use crate::parse::{
+    self, File, Key, LocalizedString, PluralValue, Section, StringValue, ANDROID_SUPPORTED_TAGS,
+};
+
+/// Keys read from the resources along with what couldn't be imported
+pub struct AndroidImport {
+    pub file: File,
+    pub warnings: Vec<String>,
+}
+
+/// Reads `<file_name>.xml` of every `values` directory of `res_dir` whose only qualifier is
+/// a locale, `values` itself holds `default_lang`. Other directories are skipped.
+pub fn read(res_dir: &Path, file_name: &str, default_lang: &str) -> Result<AndroidImport> {
+    let mut dirs: Vec<(String, String)> = Vec::new();
+    let mut warnings = Vec::new();
+    let entries = fs::read_dir(res_dir)
+        .map_err(|err| anyhow!("Cannot read {}: {}", res_dir.display(), err))?;
+    for entry in entries {
+        let dir_name = entry?.file_name().to_string_lossy().to_string();
+        if !dir_name.starts_with("values") {
+            continue;
+        }
+        if !res_dir
+            .join(&dir_name)
+            .join(format!("{}.xml", file_name))
+            .is_file()
+        {
+            continue;
+        }
+        match locale_of_dir(&dir_name, default_lang) {
+            Some(locale) => dirs.push((dir_name, locale)),
+            None => warnings.push(format!("skipped {}, it's not a locale", dir_name)),
+        }
+    }
+    // "values" goes first, so its strings win over a "values-<default_lang>" duplicate
+    dirs.sort_by(|(dir_a, locale_a), (dir_b, locale_b)| {
+        (locale_a != default_lang, locale_a, dir_a).cmp(&(
+            locale_b != default_lang,
+            locale_b,
+            dir_b,
+        ))
+    });
+
+    let mut keys: IndexMap<String, Key> = IndexMap::new();
+    for (dir_name, locale) in dirs {
+        let path = res_dir.join(&dir_name).join(format!("{}.xml", file_name));
+        let display = format!("{}/{}.xml", dir_name, file_name);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+        read_resources(&text, &locale, &mut keys, &mut warnings)
+            .map_err(|err| anyhow!("Failed to read {}: {}", display, err))?;
+    }
+    Ok(AndroidImport {
+        file: File {
+            sections: vec![Section {
+                name: None,
+                keys: keys.into_values().collect(),
+            }],
+        },
+        warnings,
+    })
+}
+
+/// Locale of a resources directory, e.g. "pt-BR" for "values-pt-rBR" and "sr-Latn"
+/// for "values-b+sr+Latn". `None` if it has qualifiers other than a locale.
+fn locale_of_dir(dir_name: &str, default_lang: &str) -> Option<String> {
+    lazy_static! {
+        static ref LOCALE_RE: Regex =
+            Regex::new(r"^([a-z]{2,3})(?:-r([A-Z]{2}|[0-9]{3}))?$").unwrap();
+    }
+    let qualifier = match dir_name.strip_prefix("values") {
+        Some("") => return Some(default_lang.to_string()),
+        Some(qualifier) => qualifier.strip_prefix('-')?,
+        None => return None,
+    };
+    let mut parts: Vec<String> = if let Some(tag) = qualifier.strip_prefix("b+") {
+        // BCP 47 tag with "+" as a separator, e.g. "b+es+419"
+        let parts: Vec<String> = tag.split('+').map(str::to_string).collect();
+        if parts
+            .iter()
+            .any(|part| !part.chars().all(char::is_alphanumeric))
+        {
+            return None;
+        }
+        parts
+    } else {
+        let caps = LOCALE_RE.captures(qualifier)?;
+        let mut parts = vec![caps[1].to_string()];
+        parts.extend(caps.get(2).map(|region| region.as_str().to_string()));
+        parts
+    };
+    // Android still uses the deprecated codes of these languages
+    parts[0] = match parts[0].as_str() {
+        "in" => "id".to_string(),
+        "iw" => "he".to_string(),
+        "ji" => "yi".to_string(),
+        lang => lang.to_string(),
+    };
+    Some(parts.join("-"))
+}
+
+fn read_resources(
+    text: &str,
+    locale: &str,
+    keys: &mut IndexMap<String, Key>,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let document = Document::parse(text)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "resources" {
+        return Err(anyhow!("no <resources> element"));
+    }
+    let mut comment: Option<String> = None;
+    for node in root.children() {
+        match node.node_type() {
+            NodeType::Comment => {
+                comment = node.text().map(collapse_whitespace);
+                continue;
+            }
+            NodeType::Text if node.text().is_some_and(|t| t.trim().is_empty()) => {
+                // A comment separated by a blank line, e.g. a license header, isn't a key's one
+                if node.text().is_some_and(|t| t.matches('\n').count() > 1) {
+                    comment = None;
+                }
+                continue;
+            }
+            NodeType::Element => (),
+            _ => {
+                comment = None;
+                continue;
+            }
+        }
+        let element = node.tag_name().name();
+        let Some(name) = node.attribute("name") else {
+            comment = None;
+            continue;
+        };
+        let value = match element {
+            "string" => StringValue::Single(parse_text(&content(text, node))?),
+            "plurals" => StringValue::Plural {
+                quantities: node
+                    .children()
+                    .filter(|item| item.has_tag_name("item"))
+                    .map(|item| {
+                        Ok(PluralValue {
+                            quantity: item.attribute("quantity").unwrap_or("other").to_string(),
+                            text: parse_text(&content(text, item))?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            _ => {
+                warnings.push(format!(
+                    "skipped <{} name=\"{}\"> of {}, only strings and plurals are imported",
+                    element, name, locale
+                ));
+                comment = None;
+                continue;
+            }
+        };
+        let key = keys.entry(name.to_string()).or_insert_with(|| Key {
+            name: name.to_string(),
+            comment: None,
+            tags: Vec::new(),
+            localizations: Vec::new(),
+        });
+        if key.comment.is_none() {
+            key.comment = comment.take();
+        }
+        comment = None;
+        if key
+            .localizations
+            .iter()
+            .any(|str| str.language_code == locale)
+        {
+            continue;
+        }
+        key.localizations.push(LocalizedString {
+            language_code: locale.to_string(),
+            value,
+        });
+    }
+    Ok(())
+}
+
+fn parse_text(raw: &str) -> Result<String> {
+    parse::parse_text(raw).map_err(|err| anyhow!(err))
+}
+
+/// Text of a `<string>` or `<item>` as it would be written in a twine file.
+/// Supported HTML tags are kept as they are, other tags like `<xliff:g>` are dropped.
+fn content(source: &str, node: Node) -> String {
+    let mut result = String::new();
+    append_content(source, node, &mut result);
+    unescape(&result)
+}
+
+fn append_content(source: &str, node: Node, result: &mut String) {
+    for child in node.children() {
+        match child.node_type() {
+            NodeType::Text => result.push_str(child.text().unwrap_or_default()),
+            NodeType::Element if ANDROID_SUPPORTED_TAGS.contains(&child.tag_name().name()) => {
+                result.push_str(&source[child.range()])
+            }
+            NodeType::Element => append_content(source, child, result),
+            _ => (),
+        }
+    }
+}
+
+/// Reverts Android escapes: whitespace outside of double quotes is collapsed,
+/// `\'`, `\"`, `\@` and `\?` become plain characters and `\uXXXX` the character.
+/// `\n` and `\t` are kept, twine files write them the same way.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('n' | 't' | '\\')) => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                Some('u') => {
+                    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                    match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        Some(c) => result.push(c),
+                        None => result.push_str(&format!("\\u{}", code)),
+                    }
+                }
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            },
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                result.push(' ');
+            }
+            c => result.push(c),
+        }
+    }
+    if text.trim().starts_with('"') && text.trim().ends_with('"') {
+        result
+    } else {
+        result.trim().to_string()
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn maps_directories_to_locales() {
+    let locale = |dir| locale_of_dir(dir, "en");
+    assert_eq!(locale("values"), Some("en".to_string()));
+    assert_eq!(locale("values-ru"), Some("ru".to_string()));
+    assert_eq!(locale("values-pt-rBR"), Some("pt-BR".to_string()));
+    assert_eq!(locale("values-es-r419"), Some("es-419".to_string()));
+    assert_eq!(locale("values-b+sr+Latn"), Some("sr-Latn".to_string()));
+    assert_eq!(locale("values-in"), Some("id".to_string()));
+    assert_eq!(locale("values-night"), None);
+    assert_eq!(locale("values-ru-land"), None);
+    assert_eq!(locale("values-v21"), None);
+}
+
+#[test]
+fn unescapes_android_texts() {
+    assert_eq!(unescape("Don\\'t \\\"quote\\\"\\n"), "Don't \"quote\"\\n");
+    assert_eq!(unescape("  many \n   spaces  "), "many spaces");
+    assert_eq!(unescape("\"  kept  \""), "  kept  ");
+    assert_eq!(unescape("\\u00e9t\\u00e9 \\@home \\?"), "été @home ?");
+}
+
+#[test]
+fn reads_strings_plurals_and_comments() -> Result<()> {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources xmlns:xliff="urn:oasis:names:tc:xliff:document:1.2">
+    <!-- Shown on
+         the start screen -->
+    <string name="greeting">Hello, <xliff:g id="name">%1$s</xliff:g> &amp; <b>friends</b>!</string>
+    <string-array name="days"><item>Monday</item></string-array>
+    <plurals name="songs">
+        <item quantity="one">%d song</item>
+        <item quantity="other">%d songs</item>
+    </plurals>
+</resources>
+"#;
+    let mut keys = IndexMap::new();
+    let mut warnings = Vec::new();
+    read_resources(xml, "en", &mut keys, &mut warnings)?;
+    let file = File {
+        sections: vec![Section {
+            name: Some("Strings".to_string()),
+            keys: keys.into_values().collect(),
+        }],
+    };
+    let expected = "[[Strings]]
+  [greeting]
+    comment = Shown on the start screen
+    en = Hello, %1$s & <b>friends</b>!
+
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+";
+    assert_eq!(crate::twine::render(&file), expected);
+    assert_eq!(
+        warnings,
+        vec!["skipped <string-array name=\"days\"> of en, only strings and plurals are imported"]
+    );
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use anyhow::{Ok, Result};
+use rayon::prelude::*;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::generator::{GenResult, Generator, Line, StrLines};
+use crate::output::OutputFiles;
+use crate::parse::{self, PluralValue, StringValue};
+use crate::pipeline::{GenOptions, Source};
+use crate::web_gen::{self, DefaultPlurals};
+
+/// Name of the plural selector argument, Flutter passes the quantity with it
+const COUNT: &str = "count";
+
+/// Writes `app_<locale>.arb` with keys of all sources for every locale.
+///
+/// Texts quote ICU syntax characters, Flutter reads them with `use-escaping: true` in `l10n.yaml`.
+pub struct ArbGenerator;
+
+impl Generator for ArbGenerator {
+    fn name(&self) -> &str {
+        "arb"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let generated = GenResult::from_files(sources.iter().map(|src| &src.file))?;
+        render(&generated, &options.output_dir, &options.default_lang)
+    }
+}
+
+pub fn render(
+    generated: &GenResult,
+    dir: &Path,
+    default_lang: &Option<String>,
+) -> Result<OutputFiles> {
+    let defaults = web_gen::default_plurals(generated, default_lang);
+    generated
+        .value
+        .par_iter()
+        .map(|(locale, lines)| {
+            // Flutter names locales with underscores, e.g. "app_en_GB.arb"
+            let locale = locale.value.replace('-', "_");
+            let path = dir.join(format!("app_{}.arb", locale));
+            Ok((path, render_locale(&locale, lines, &defaults)?))
+        })
+        .collect()
+}
+
+fn render_locale(locale: &str, lines: &StrLines, defaults: &DefaultPlurals) -> Result<String> {
+    let mut entries = vec![("@@locale".to_string(), json!(locale))];
+    for line in &lines.value {
+        let (text, placeholders) = convert(line.value, defaults.get(line.name).copied());
+        entries.push((line.name.to_string(), json!(text)));
+        let metadata = metadata(line, placeholders);
+        if !metadata.is_empty() {
+            entries.push((format!("@{}", line.name), Value::Object(metadata)));
+        }
+    }
+    let mut result = serde_json::to_string_pretty(&Entries(entries))?;
+    result.push('\n');
+    Ok(result)
+}
+
+fn metadata(line: &Line, placeholders: Map<String, Value>) -> Map<String, Value> {
+    let mut metadata = Map::new();
+    if let Some(comment) = line.comment {
+        metadata.insert("description".to_string(), json!(comment));
+    }
+    if !placeholders.is_empty() {
+        metadata.insert("placeholders".to_string(), Value::Object(placeholders));
+    }
+    metadata
+}
+
+/// Message in ICU syntax along with metadata of its placeholders,
+/// `default` is the plural of the default language with the same name
+fn convert(value: &StringValue, default: Option<&[PluralValue]>) -> (String, Map<String, Value>) {
+    let mut placeholders = Map::new();
+    let text = match value {
+        StringValue::Single(text) => convert_text(text, false, None, &mut placeholders),
+        StringValue::Plural { quantities } => {
+            let quantities = web_gen::icu_quantities(quantities, default);
+            convert_plural(&quantities, &mut placeholders)
+        }
+    };
+    (text, placeholders)
+}
+
+/// `{count, plural, one{...} other{...}}`, the first integer placeholder is the count
+fn convert_plural(quantities: &[PluralValue], placeholders: &mut Map<String, Value>) -> String {
+    let count_position = web_gen::count_position(quantities);
+    placeholders.insert(COUNT.to_string(), json!({ "type": "int" }));
+    let branches: Vec<String> = quantities
+        .iter()
+        .map(|plural| {
+            format!(
+                "{}{{{}}}",
+                plural.quantity,
+                convert_text(&plural.text, true, count_position, placeholders)
+            )
+        })
+        .collect();
+    format!("{{{}, plural, {}}}", COUNT, branches.join(" "))
+}
+
+/// Quotes ICU syntax characters and replaces placeholders with named `{argN}` ones,
+/// `count_position` is named `{count}`
+fn convert_text(
+    text: &str,
+    in_plural: bool,
+    count_position: Option<usize>,
+    placeholders: &mut Map<String, Value>,
+) -> String {
+    let text = web_gen::escape_icu(&parse::unescape(text), in_plural);
+    parse::replace_placeholders(&text, "%", |placeholder| {
+        let name = if count_position == Some(placeholder.position) {
+            COUNT.to_string()
+        } else {
+            format!("arg{}", placeholder.position)
+        };
+        if !placeholders.contains_key(&name) {
+            placeholders.insert(name.clone(), placeholder_metadata(placeholder));
+        }
+        format!("{{{}}}", name)
+    })
+}
+
+fn placeholder_metadata(placeholder: &parse::Placeholder) -> Value {
+    if placeholder.is_integer() {
+        return json!({ "type": "int" });
+    }
+    if !"fFeEgGaA".contains(placeholder.conversion) {
+        return json!({ "type": "String" });
+    }
+    match placeholder
+        .modifiers()
+        .split_once('.')
+        .and_then(|(_, digits)| digits.parse::<u32>().ok())
+    {
+        Some(digits) => json!({
+            "type": "double",
+            "format": "decimalPatternDigits",
+            "optionalParameters": { "decimalDigits": digits },
+        }),
+        None => json!({ "type": "double" }),
+    }
+}
+
+/// JSON object keeping the order of its entries, so metadata follows its key
+struct Entries(Vec<(String, Value)>);
+
+impl Serialize for Entries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn plural_val(quantity: &str, text: &str) -> PluralValue {
+    PluralValue {
+        quantity: quantity.to_string(),
+        text: text.to_string(),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn converts_placeholders_to_named_ones() {
+    let (text, placeholders) = convert(
+        &StringValue::Single("%1$s paid %2$.2f, that\\'s 100%% {sic}".to_string()),
+        None,
+    );
+    assert_eq!(text, "{arg1} paid {arg2}, that''s 100% '{'sic'}'");
+    assert_eq!(
+        Value::Object(placeholders),
+        json!({
+            "arg1": { "type": "String" },
+            "arg2": {
+                "type": "double",
+                "format": "decimalPatternDigits",
+                "optionalParameters": { "decimalDigits": 2 },
+            },
+        })
+    );
+}
+
+#[test]
+fn converts_plurals_to_icu() {
+    let (text, placeholders) = convert(
+        &StringValue::Plural {
+            quantities: vec![
+                plural_val("one", "%1$d song by %2$s"),
+                plural_val("other", "%1$d songs by %2$s"),
+            ],
+        },
+        None,
+    );
+    assert_eq!(
+        text,
+        "{count, plural, one{{count} song by {arg2}} other{{count} songs by {arg2}}}"
+    );
+    assert_eq!(
+        Value::Object(placeholders),
+        json!({ "count": { "type": "int" }, "arg2": { "type": "String" } })
+    );
+}
+
+#[test]
+fn completes_plurals_with_other() {
+    let default = [
+        plural_val("one", "%d song"),
+        plural_val("other", "%d songs"),
+    ];
+    let ru = StringValue::Plural {
+        quantities: vec![plural_val("one", "%d песня"), plural_val("few", "%d песни")],
+    };
+    assert_eq!(
+        convert(&ru, Some(&default)).0,
+        "{count, plural, one{{count} песня} few{{count} песни} other{{count} songs}}"
+    );
+    assert_eq!(
+        convert(&ru, None).0,
+        "{count, plural, one{{count} песня} few{{count} песни} other{{count} песни}}"
+    );
+}
+
+#[test]
+fn keeps_metadata_next_to_its_key() -> Result<()> {
+    let value = StringValue::Single("Hi %s".to_string());
+    let lines = StrLines {
+        value: vec![Line {
+            name: "greeting",
+            comment: Some("Greets the user"),
+            tags: &[],
+            value: &value,
+        }],
+    };
+    let expected = r#"{
+  "@@locale": "en_GB",
+  "greeting": "Hi {arg1}",
+  "@greeting": {
+    "description": "Greets the user",
+    "placeholders": {
+      "arg1": {
+        "type": "String"
+      }
+    }
+  }
+}
+"#;
+    assert_eq!(
+        render_locale("en_GB", &lines, &DefaultPlurals::new())?,
+        expected
+    );
+    Ok(())
+}
@@ -0,0 +1,567 @@
+//! Command line interface, the `utas` binary only calls [`run`].
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use crate::android_import;
+use crate::diff::{self, DiffFormat};
+use crate::exchange::{self, ImportReport, Translation};
+use crate::generator::Registry;
+use crate::input::{self, InputFilter};
+use crate::ios_import;
+use crate::kotlin_gen::KotlinGenerator;
+use crate::lock::Lock;
+use crate::output::{self, OutputFiles};
+use crate::pipeline::{self, ErrorMode, GenOptions, Source};
+use crate::plugin_gen::PluginGenerator;
+use crate::po;
+use crate::pseudo::{self, PseudoLocale, PseudoOptions};
+use crate::spreadsheet;
+use crate::stats::{self, StatsFormat};
+use crate::template_gen::TemplateGenerator;
+use crate::ts_gen::TypesGenerator;
+use crate::twine::{self, TwineDocument};
+use crate::web_gen::{KeyMode, PlaceholderStyle, PluralStyle, WebGenerator};
+use crate::xliff::{self, XliffVersion};
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Comma separated list of platforms to generate, e.g. "android,ios".
+    /// "template" renders the file given with --template, plugins are selected by their name
+    #[arg(value_parser = parse_platforms, required = true)]
+    platforms: Option<Platforms>,
+    /// Twine file or directory with twine files
+    #[arg(required = true)]
+    input_dir: Option<PathBuf>,
+    #[arg(required = true)]
+    output_dir: Option<PathBuf>,
+    default_lang: Option<String>,
+    file_name: Option<String>,
+    #[command(flatten)]
+    input: InputArgs,
+    /// Minijinja template rendered by the "template" platform
+    #[arg(long, value_name = "FILE", requires = "template_output")]
+    template: Option<PathBuf>,
+    /// Path of the rendered template relative to the output directory,
+    /// rendered once per locale if it contains "{locale}", e.g. "{locale}/strings.json"
+    #[arg(long, value_name = "PATH", requires = "template")]
+    template_output: Option<String>,
+    /// How the "web" platform writes key names
+    #[arg(long, value_enum, default_value_t = KeyMode::Nested)]
+    web_keys: KeyMode,
+    /// How the "web" platform writes plurals
+    #[arg(long, value_enum, default_value_t = PluralStyle::I18next)]
+    web_plurals: PluralStyle,
+    /// How the "web" platform writes placeholders
+    #[arg(long, value_enum, default_value_t = PlaceholderStyle::Named)]
+    web_placeholders: PlaceholderStyle,
+    /// Package of the objects written by the "kotlin" platform, `R` is imported from it
+    #[arg(long, value_name = "PACKAGE")]
+    kotlin_package: Option<String>,
+    /// Directory of the files written by the "kotlin" platform, relative to the output
+    /// directory unless absolute, e.g. "../java/com/example/app"
+    #[arg(long, value_name = "DIR")]
+    kotlin_output_dir: Option<PathBuf>,
+    /// Comma separated pseudo locales made from the default language, e.g. "en-XA,ar-XB"
+    #[arg(long, value_enum, value_delimiter = ',', requires = "default_lang")]
+    pseudo: Vec<PseudoLocale>,
+    /// Length added to "en-XA" texts, in percent
+    #[arg(long, value_name = "PERCENT", default_value_t = 30)]
+    pseudo_padding: usize,
+    /// Don't wrap "en-XA" texts in brackets
+    #[arg(long)]
+    pseudo_no_brackets: bool,
+    /// Lock file with the default language texts translations were confirmed against,
+    /// translations of changed texts are reported as stale. Created if it doesn't exist.
+    #[arg(long, value_name = "FILE", requires = "default_lang")]
+    lock: Option<PathBuf>,
+    /// External generator reading sources as JSON from stdin, e.g. "web=node gen.js"
+    #[arg(long = "plugin", value_name = "NAME=COMMAND")]
+    plugins: Vec<String>,
+}
+
+/// Input flags shared by all commands
+#[derive(clap::Args)]
+struct InputArgs {
+    /// Additional twine files or directories to read
+    #[arg(long = "input", value_name = "PATH")]
+    inputs: Vec<PathBuf>,
+    /// Walk input directories recursively
+    #[arg(short, long)]
+    recursive: bool,
+    /// Only read files matching the glob, relative to the input directory, e.g. "**/*.twine"
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip files matching the glob, relative to the input directory
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// How to report sources that fail to parse
+    #[arg(long, value_enum, default_value_t = ErrorMode::FailFast)]
+    errors: ErrorMode,
+}
+
+impl InputArgs {
+    fn inputs(&self, input_dir: PathBuf) -> Vec<PathBuf> {
+        let mut inputs = vec![input_dir];
+        inputs.extend(self.inputs.iter().cloned());
+        inputs
+    }
+
+    fn filter(&self) -> Result<InputFilter> {
+        InputFilter::new(self.recursive, &self.include, &self.exclude)
+    }
+
+    fn load_sources(&self, input_dir: PathBuf) -> Result<Vec<Source>> {
+        pipeline::load_sources(&self.inputs(input_dir), &self.filter()?, self.errors)
+    }
+
+    fn paths(&self, input_dir: PathBuf) -> Result<Vec<PathBuf>> {
+        input::collect_sources(&self.inputs(input_dir), &self.filter()?)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write files for translators
+    #[command(subcommand)]
+    Export(Export),
+    /// Merge translated files back into twine files
+    #[command(subcommand)]
+    Import(Import),
+    /// Rewrite twine files in the canonical layout: keys sorted by name,
+    /// the default language first and the other locales alphabetically
+    Fmt {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        /// Locale written first in every key
+        default_lang: String,
+        /// List the files which aren't formatted instead of rewriting them,
+        /// failing if there are any
+        #[arg(long)]
+        check: bool,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Compare two versions of twine sources by key: keys added, removed and renamed,
+    /// and texts changed per locale and plural quantity. Files given with --input
+    /// belong to both versions
+    Diff {
+        /// Old twine file or directory with twine files
+        old: PathBuf,
+        /// New twine file or directory with twine files
+        new: PathBuf,
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Mark translations as reviewed in the lock file, so they are no longer stale
+    /// after their default language text changed
+    Review {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        default_lang: String,
+        /// Lock file to update
+        lock: PathBuf,
+        /// Keys to mark reviewed
+        #[arg(required_unless_present = "all")]
+        keys: Vec<String>,
+        /// Mark all keys reviewed
+        #[arg(long, conflicts_with = "keys")]
+        all: bool,
+        /// Only mark translations into the locale reviewed
+        #[arg(long)]
+        locale: Vec<String>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Report how complete every locale is compared to the default language,
+    /// in total and per source
+    Stats {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        default_lang: String,
+        #[arg(long, value_enum, default_value_t = StatsFormat::Table)]
+        format: StatsFormat,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum Export {
+    /// An XLIFF file per target locale, named "<locale>.xlf"
+    Xliff {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        output_dir: PathBuf,
+        default_lang: String,
+        #[command(flatten)]
+        input: InputArgs,
+        #[arg(long, value_enum, default_value_t = XliffVersion::V1_2)]
+        xliff_version: XliffVersion,
+        /// Locale to export, all locales but the default one if not set
+        #[arg(long)]
+        locale: Vec<String>,
+    },
+    /// A gettext PO file per target locale, named "<locale>.po", and "messages.pot"
+    Po {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        output_dir: PathBuf,
+        default_lang: String,
+        #[command(flatten)]
+        input: InputArgs,
+        /// Locale to export, all locales but the default one if not set
+        #[arg(long)]
+        locale: Vec<String>,
+    },
+    /// A CSV file with a column per locale and a row per key or plural quantity
+    Csv {
+        /// Twine file or directory with twine files
+        input_dir: PathBuf,
+        /// CSV file to write
+        output: PathBuf,
+        /// Locale of the first locale column
+        default_lang: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum Import {
+    /// Translated XLIFF 1.2 or 2.0 files
+    Xliff {
+        /// Twine file or directory with twine files to update
+        input_dir: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Translated gettext PO files
+    Po {
+        /// Twine file or directory with twine files to update
+        input_dir: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// An edited CSV file, keys which only exist in it are added to the first twine file
+    Csv {
+        /// Twine file or directory with twine files to update
+        input_dir: PathBuf,
+        file: PathBuf,
+        /// The CSV file as it was exported, cells changed in both the file and
+        /// the twine files since then are reported instead of imported
+        #[arg(long, value_name = "FILE")]
+        base: Option<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Android "values*/strings.xml" resources, written as a new twine file
+    Android {
+        /// Resources directory with the "values" directories
+        res_dir: PathBuf,
+        /// Twine file to write
+        output: PathBuf,
+        /// Locale of the strings in "values"
+        default_lang: String,
+        /// Name of the resource files to read, without ".xml"
+        #[arg(long, default_value = "strings")]
+        file_name: String,
+        /// Name of the twine section holding the keys
+        #[arg(long, default_value = "Strings")]
+        section: String,
+    },
+    /// iOS "*.lproj" strings tables, merged with the keys of the twine files.
+    /// Unknown keys are added to the first file, differing texts are reported as conflicts.
+    Ios {
+        /// Directory with the ".lproj" directories
+        lproj_dir: PathBuf,
+        /// Twine file or directory with twine files to update
+        input_dir: PathBuf,
+        /// Locale of the strings in "Base.lproj"
+        default_lang: String,
+        /// Name of the strings table, without ".strings"
+        #[arg(long, default_value = "Localizable")]
+        table: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Platforms(Vec<String>);
+
+fn parse_platforms(value: &str) -> Result<Platforms, String> {
+    // Names are checked against the registry once it's built from all arguments
+    Result::Ok(Platforms(
+        value
+            .split(',')
+            .map(|platform| platform.trim().to_lowercase())
+            .collect(),
+    ))
+}
+
+/// Runs the command line interface with the arguments of the process
+pub fn run() -> Result<()> {
+    let args = Args::parse();
+    match args.command {
+        Some(Command::Export(export)) => run_export(export),
+        Some(Command::Import(import)) => run_import(import),
+        Some(Command::Fmt {
+            input_dir,
+            default_lang,
+            check,
+            input,
+        }) => run_fmt(input_dir, &default_lang, check, &input),
+        Some(Command::Diff {
+            old,
+            new,
+            format,
+            input,
+        }) => {
+            let diff = diff::diff(&input.load_sources(old)?, &input.load_sources(new)?);
+            print!("{}", diff.render(format)?);
+            Ok(())
+        }
+        Some(Command::Review {
+            input_dir,
+            default_lang,
+            lock,
+            keys,
+            all: _,
+            locale,
+            input,
+        }) => {
+            let sources = input.load_sources(input_dir)?;
+            let mut reviewed = Lock::read(&lock)?;
+            // Translations the lock doesn't know yet are recorded too
+            reviewed.update(&sources, &default_lang);
+            let count = reviewed.review(&sources, &default_lang, &keys, &locale);
+            output::write_files(&OutputFiles::from([(lock, reviewed.render()?)]))?;
+            println!("{} stale translations marked reviewed", count);
+            Ok(())
+        }
+        Some(Command::Stats {
+            input_dir,
+            default_lang,
+            format,
+            input,
+        }) => {
+            let sources = input.load_sources(input_dir)?;
+            print!("{}", stats::collect(&sources, &default_lang)?.render(format)?);
+            Ok(())
+        }
+        None => run_gen(args),
+    }
+}
+
+fn run_gen(args: Args) -> Result<()> {
+    // Clap requires the positional arguments when there is no subcommand
+    let inputs = args.input.inputs(args.input_dir.unwrap());
+    let filter = args.input.filter()?;
+    let options = GenOptions {
+        output_dir: args.output_dir.unwrap(),
+        default_lang: args.default_lang,
+        file_name: args.file_name,
+    };
+    let mut registry = Registry::builtin();
+    registry.register(Box::new(WebGenerator {
+        keys: args.web_keys,
+        plurals: args.web_plurals,
+        placeholders: args.web_placeholders,
+    }));
+    registry.register(Box::new(TypesGenerator {
+        plurals: args.web_plurals,
+        placeholders: args.web_placeholders,
+    }));
+    registry.register(Box::new(KotlinGenerator {
+        package: args.kotlin_package,
+        output_dir: args.kotlin_output_dir,
+    }));
+    if let (Some(template), Some(output)) = (args.template, args.template_output) {
+        registry.register(Box::new(TemplateGenerator::from_file(&template, output)?));
+    }
+    for plugin in &args.plugins {
+        registry.register(Box::new(PluginGenerator::from_spec(plugin)?));
+    }
+    let platforms = args.platforms.unwrap().0;
+    if args.pseudo.is_empty() && args.lock.is_none() {
+        return pipeline::run_gen_pipeline(
+            &registry,
+            &platforms,
+            &inputs,
+            &filter,
+            args.input.errors,
+            &options,
+        );
+    }
+    pipeline::find_generators(&registry, &platforms)?;
+    let mut sources = pipeline::load_sources(&inputs, &filter, args.input.errors)?;
+    // Clap requires the default language with pseudo locales and the lock
+    let default_lang = options.default_lang.as_deref().unwrap();
+    if let Some(path) = &args.lock {
+        let mut lock = Lock::read(path)?;
+        for stale in lock.update(&sources, default_lang) {
+            eprintln!("warning: {}", stale);
+        }
+        output::write_files(&OutputFiles::from([(path.clone(), lock.render()?)]))?;
+    }
+    if !args.pseudo.is_empty() {
+        let pseudo_options = PseudoOptions {
+            padding: args.pseudo_padding,
+            brackets: !args.pseudo_no_brackets,
+        };
+        pseudo::add_locales(&mut sources, default_lang, &args.pseudo, &pseudo_options)?;
+    }
+    pipeline::generate(&registry, &platforms, &sources, &options)
+}
+
+fn run_export(export: Export) -> Result<()> {
+    let files = match export {
+        Export::Xliff {
+            input_dir,
+            output_dir,
+            default_lang,
+            input,
+            xliff_version,
+            locale,
+        } => {
+            let sources = input.load_sources(input_dir)?;
+            xliff::export(&sources, &default_lang, &locale, xliff_version, &output_dir)?
+        }
+        Export::Po {
+            input_dir,
+            output_dir,
+            default_lang,
+            input,
+            locale,
+        } => {
+            let sources = input.load_sources(input_dir)?;
+            po::export(&sources, &default_lang, &locale, &output_dir)?
+        }
+        Export::Csv {
+            input_dir,
+            output,
+            default_lang,
+            input,
+        } => {
+            let sources = input.load_sources(input_dir)?;
+            OutputFiles::from([(output, spreadsheet::export(&sources, &default_lang)?)])
+        }
+    };
+    output::write_files(&files)
+}
+
+fn run_fmt(input_dir: PathBuf, default_lang: &str, check: bool, input: &InputArgs) -> Result<()> {
+    let mut unformatted = OutputFiles::new();
+    for path in input.paths(input_dir)? {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+        let formatted = TwineDocument::parse(&text).format(default_lang);
+        if formatted != text {
+            unformatted.insert(path, formatted);
+        }
+    }
+    if !check {
+        return output::write_files(&unformatted);
+    }
+    for path in unformatted.keys() {
+        println!("{}", path.display());
+    }
+    if !unformatted.is_empty() {
+        return Err(anyhow!("{} files are not formatted", unformatted.len()));
+    }
+    Ok(())
+}
+
+fn run_import(import: Import) -> Result<()> {
+    match import {
+        Import::Xliff {
+            input_dir,
+            files,
+            input,
+        } => import_translations(input_dir, &files, &input, xliff::read),
+        Import::Po {
+            input_dir,
+            files,
+            input,
+        } => import_translations(input_dir, &files, &input, po::read),
+        Import::Csv {
+            input_dir,
+            file,
+            base,
+            input,
+        } => {
+            let paths = input.paths(input_dir)?;
+            let (changed, report) = spreadsheet::import(&paths, &file, base.as_deref())?;
+            write_import(&changed, &report)
+        }
+        Import::Android {
+            res_dir,
+            output,
+            default_lang,
+            file_name,
+            section,
+        } => {
+            let mut imported = android_import::read(&res_dir, &file_name, &default_lang)?;
+            for warning in &imported.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            for imported_section in &mut imported.file.sections {
+                imported_section.name = Some(section.clone());
+            }
+            let contents = twine::render(&imported.file);
+            output::write_files(&OutputFiles::from([(output, contents)]))
+        }
+        Import::Ios {
+            lproj_dir,
+            input_dir,
+            default_lang,
+            table,
+            input,
+        } => {
+            let imported = ios_import::read(&lproj_dir, &table, &default_lang)?;
+            for warning in &imported.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            let keys: Vec<_> = imported
+                .file
+                .sections
+                .into_iter()
+                .flat_map(|section| section.keys)
+                .collect();
+            let (changed, report) = exchange::merge_keys(&input.paths(input_dir)?, &keys, "iOS")?;
+            write_import(&changed, &report)
+        }
+    }
+}
+
+fn import_translations(
+    input_dir: PathBuf,
+    files: &[PathBuf],
+    input: &InputArgs,
+    read: fn(&Path) -> Result<Vec<Translation>>,
+) -> Result<()> {
+    let mut translations = Vec::new();
+    for file in files {
+        translations.extend(read(file)?);
+    }
+    let (changed, report) = exchange::merge(&input.paths(input_dir)?, &translations)?;
+    write_import(&changed, &report)
+}
+
+fn write_import(changed: &OutputFiles, report: &ImportReport) -> Result<()> {
+    output::write_files(changed)?;
+    // Conflicts are only reported, the rest of the translations is still merged
+    println!("{}", report.describe());
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use anyhow::{anyhow, Ok, Result};
+use lazy_static::lazy_static;
+use rayon::prelude::*;
+use regex::Regex;
+use std::path::Path;
+
+use crate::android_gen::{self, render_resources_with};
+use crate::escape;
+use crate::generator::{GenResult, Generator};
+#[cfg(test)]
+use crate::generator::{Line, Locale, StrLines};
+use crate::output::OutputFiles;
+use crate::parse;
+#[cfg(test)]
+use crate::parse::{PluralValue, StringValue};
+use crate::pipeline::{GenOptions, Source};
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+/// Writes `composeResources/values-<locale>/<source name>.xml` for every source
+pub struct ComposeGenerator;
+
+impl Generator for ComposeGenerator {
+    fn name(&self) -> &str {
+        "compose"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let dir = options.output_dir.join("composeResources");
+        let rendered = sources
+            .par_iter()
+            .map(|source| {
+                let generated = android_gen::generate(&source.file)?;
+                render(&generated, &dir, &source.name, &options.default_lang)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(rendered.into_iter().flatten().collect())
+    }
+
+    fn file_per_source(&self) -> bool {
+        true
+    }
+}
+
+/// Renders `values-<locale>/<file_name>.xml` files, the default locale is also rendered into `values`
+pub fn render(
+    generated: &GenResult,
+    dir: &Path,
+    file_name: &str,
+    default_lang: &Option<String>,
+) -> Result<OutputFiles> {
+    let rendered = generated
+        .value
+        .par_iter()
+        .map(|(locale, lines)| {
+            let file = format!("{}.xml", file_name);
+            let contents = render_resources_with(lines, &convert_text);
+
+            let mut files = Vec::with_capacity(2);
+            if default_lang.as_ref() == Some(&locale.value) {
+                files.push((dir.join("values").join(&file), contents.clone()));
+            }
+            files.push((
+                dir.join(values_dir_name(&locale.value)?).join(&file),
+                contents,
+            ));
+            Ok(files)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rendered.into_iter().flatten().collect())
+}
+
+/// Name of the resources directory for a twine locale, e.g. "values-en-rGB" for "en-GB".
+///
+/// Compose resources only have a language qualifier with an optional two letter region,
+/// Chinese scripts are mapped to the regions using them.
+pub fn values_dir_name(locale: &str) -> Result<String> {
+    let unsupported = || {
+        anyhow!(
+            "Locale \"{}\" has no Compose resources qualifier, expected a language with an optional two letter region",
+            locale
+        )
+    };
+    let mut parts = locale.split(['-', '_']);
+    let lang = parts.next().unwrap_or_default().to_lowercase();
+    if !(2..=3).contains(&lang.len()) || !lang.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(unsupported());
+    }
+    let region = match (lang.as_str(), parts.next(), parts.next()) {
+        (_, None, _) => None,
+        ("zh", Some("Hans"), None) => Some("CN".to_string()),
+        ("zh", Some("Hant"), None) => Some("TW".to_string()),
+        (_, Some(region), None)
+            if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) =>
+        {
+            Some(region.to_uppercase())
+        }
+        _ => return Err(unsupported()),
+    };
+    Ok(match region {
+        Some(region) => format!("values-{}-r{}", lang, region),
+        None => format!("values-{}", lang),
+    })
+}
+
+/// Converts a parsed text to the Compose resources syntax.
+///
+/// Compose resources don't process Android escapes and styling tags, so the tags are stripped
+/// and the text is unescaped and written as plain XML text. Only numbered "%1$s" and "%1$d"
+/// placeholders are formatted and "%%" isn't an escape there.
+fn convert_text(text: &str) -> String {
+    let plain = parse::unescape(&strip_tags(text));
+    let numbered = parse::replace_placeholders(&plain, "%", |placeholder| {
+        if placeholder.is_numbered() {
+            placeholder.spec.clone()
+        } else {
+            format!(
+                "%{}${}{}",
+                placeholder.position,
+                placeholder.modifiers(),
+                placeholder.conversion
+            )
+        }
+    });
+    escape::xml(&numbered)
+}
+
+/// Removes the markup of the styling tags `parse` kept in a parsed text, keeping their contents
+fn strip_tags(text: &str) -> String {
+    lazy_static! {
+        static ref MARKUP_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end) in parse::html_tag_regions(text) {
+        if start < position {
+            // nested in the previous region, its markup is already removed
+            continue;
+        }
+        result.push_str(&text[position..start]);
+        result.push_str(&MARKUP_RE.replace_all(&text[start..end], ""));
+        position = end;
+    }
+    result.push_str(&text[position..]);
+    result
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn single(name: &'static str, text: &str) -> Line<'static> {
+    Line {
+        name,
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Single(text.to_string()))),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn maps_locales_to_qualifiers() -> Result<()> {
+    assert_eq!(values_dir_name("en")?, "values-en");
+    assert_eq!(values_dir_name("en-GB")?, "values-en-rGB");
+    assert_eq!(values_dir_name("pt_br")?, "values-pt-rBR");
+    assert_eq!(values_dir_name("zh-Hans")?, "values-zh-rCN");
+    assert_eq!(values_dir_name("zh-Hant")?, "values-zh-rTW");
+    assert!(values_dir_name("es-419").is_err());
+    assert!(values_dir_name("sr-Latn-RS").is_err());
+    Ok(())
+}
+
+#[test]
+fn converts_texts() {
+    assert_eq!(
+        convert_text("Tom\\'s \\\"%s\\\" &amp; <b>%d</b> 5%%"),
+        "Tom&apos;s &quot;%1$s&quot; &amp; %2$d 5%"
+    );
+    assert_eq!(convert_text("%1$s of %2$d"), "%1$s of %2$d");
+}
+
+#[test]
+fn strips_styling_tags() {
+    let parsed = parse::parse_text("Don't <b>stop</b> <i>now</i> & <later>").unwrap();
+    assert_eq!(
+        convert_text(&parsed),
+        "Don&apos;t stop now &amp; &lt;later&gt;"
+    );
+    assert_eq!(
+        convert_text("<a href=\"https://example.com\"><b>Home</b></a>"),
+        "Home"
+    );
+}
+
+#[test]
+fn renders_default_locale_twice() -> Result<()> {
+    let songs = Line {
+        name: "songs",
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Plural {
+            quantities: vec![PluralValue {
+                quantity: "other".to_string(),
+                text: "%d songs".to_string(),
+            }],
+        })),
+    };
+    let generated = GenResult {
+        value: BTreeMap::from([(
+            Locale {
+                value: "en".to_string(),
+            },
+            StrLines {
+                value: vec![single("find", "Find"), songs],
+            },
+        )]),
+    };
+    let actual = render(
+        &generated,
+        Path::new("res"),
+        "strings",
+        &Some("en".to_string()),
+    )?;
+    let contents = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\n<resources>\n  <string name=\"find\">Find</string>\n  <plurals name=\"songs\">\n    <item quantity=\"other\">%1$d songs</item>\n  </plurals>\n</resources>\n";
+    let expected = OutputFiles::from([
+        (
+            Path::new("res/values/strings.xml").to_path_buf(),
+            contents.to_string(),
+        ),
+        (
+            Path::new("res/values-en/strings.xml").to_path_buf(),
+            contents.to_string(),
+        ),
+    ]);
+    assert_eq!(expected, actual);
+    Ok(())
+}
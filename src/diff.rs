@@ -0,0 +1,340 @@
+//! Differences between two versions of twine sources by key, instead of by line.
+//!
+//! Keys are identified by their name and whether they are plurals, like generators tell
+//! a single string and a plural of the same name apart. The source a key is in doesn't
+//! matter, so keys moved between files are unchanged.
+
+use anyhow::{Ok, Result};
+use clap::ValueEnum;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::parse::{Key, StringValue};
+use crate::pipeline::Source;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Serialize, Default, PartialEq, Eq, Debug)]
+pub struct Diff {
+    pub added: Vec<KeyName>,
+    pub removed: Vec<KeyName>,
+    pub renamed: Vec<Renamed>,
+    pub changed: Vec<KeyChanges>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct KeyName {
+    pub name: String,
+    pub plural: bool,
+}
+
+/// A removed key whose texts an added key has in every locale
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct Renamed {
+    pub from: KeyName,
+    pub to: KeyName,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct KeyChanges {
+    pub key: KeyName,
+    pub comment: Option<CommentChange>,
+    pub values: Vec<ValueChange>,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct CommentChange {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A text of a locale, or of a plural quantity, which was added, removed or changed
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct ValueChange {
+    pub locale: String,
+    pub quantity: Option<String>,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+type Texts<'a> = IndexMap<(&'a str, Option<&'a str>), &'a str>;
+
+/// Compares the keys of `old` and `new`, of keys with the same name and kind the first one counts
+pub fn diff(old: &[Source], new: &[Source]) -> Diff {
+    let old = keys(old);
+    let new = keys(new);
+    let mut result = Diff::default();
+    for (id, old_key) in &old {
+        match new.get(id) {
+            Some(new_key) => {
+                if let Some(changes) = key_changes(old_key, new_key) {
+                    result.changed.push(changes);
+                }
+            }
+            None => result.removed.push(key_name(old_key)),
+        }
+    }
+    let mut added: Vec<&Key> = new
+        .iter()
+        .filter(|(id, _)| !old.contains_key(*id))
+        .map(|(_, key)| *key)
+        .collect();
+
+    // A removed key becomes a rename if an added key has the same texts
+    result.removed.retain(|removed| {
+        let old_key = old[&(removed.name.as_str(), removed.plural)];
+        let old_texts = texts(old_key);
+        let renamed_to = added.iter().position(|new_key| {
+            is_plural(new_key) == removed.plural
+                && !old_texts.is_empty()
+                && texts(new_key) == old_texts
+        });
+        match renamed_to {
+            Some(index) => {
+                result.renamed.push(Renamed {
+                    from: removed.clone(),
+                    to: key_name(added.remove(index)),
+                });
+                false
+            }
+            None => true,
+        }
+    });
+    result.added = added.into_iter().map(key_name).collect();
+    result
+}
+
+fn keys(sources: &[Source]) -> IndexMap<(&str, bool), &Key> {
+    let mut result = IndexMap::new();
+    for key in sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys)
+    {
+        result
+            .entry((key.name.as_str(), is_plural(key)))
+            .or_insert(key);
+    }
+    result
+}
+
+fn is_plural(key: &Key) -> bool {
+    key.localizations
+        .iter()
+        .any(|str| matches!(str.value, StringValue::Plural { .. }))
+}
+
+fn key_name(key: &Key) -> KeyName {
+    KeyName {
+        name: key.name.clone(),
+        plural: is_plural(key),
+    }
+}
+
+/// Texts of a key by locale and quantity, in the order of the twine file
+fn texts(key: &Key) -> Texts<'_> {
+    let mut result = IndexMap::new();
+    for str in &key.localizations {
+        let locale = str.language_code.as_str();
+        match &str.value {
+            StringValue::Single(text) => {
+                result.entry((locale, None)).or_insert(text.as_str());
+            }
+            StringValue::Plural { quantities } => {
+                for plural in quantities {
+                    result
+                        .entry((locale, Some(plural.quantity.as_str())))
+                        .or_insert(plural.text.as_str());
+                }
+            }
+        }
+    }
+    result
+}
+
+fn key_changes(old: &Key, new: &Key) -> Option<KeyChanges> {
+    let old_texts = texts(old);
+    let new_texts = texts(new);
+    let change =
+        |(locale, quantity): (&str, Option<&str>), old: Option<&str>, new: Option<&str>| {
+            ValueChange {
+                locale: locale.to_string(),
+                quantity: quantity.map(str::to_string),
+                old: old.map(str::to_string),
+                new: new.map(str::to_string),
+            }
+        };
+    let mut values = Vec::new();
+    for (id, old_text) in &old_texts {
+        match new_texts.get(id) {
+            Some(new_text) if new_text == old_text => (),
+            new_text => values.push(change(*id, Some(old_text), new_text.copied())),
+        }
+    }
+    for (id, new_text) in &new_texts {
+        if !old_texts.contains_key(id) {
+            values.push(change(*id, None, Some(new_text)));
+        }
+    }
+    let comment = (old.comment != new.comment).then(|| CommentChange {
+        old: old.comment.clone(),
+        new: new.comment.clone(),
+    });
+    if values.is_empty() && comment.is_none() {
+        return None;
+    }
+    Some(KeyChanges {
+        key: key_name(new),
+        comment,
+        values,
+    })
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    pub fn render(&self, format: DiffFormat) -> Result<String> {
+        let mut result = String::new();
+        match format {
+            DiffFormat::Json => {
+                result = serde_json::to_string_pretty(self)?;
+                result.push('\n');
+            }
+            DiffFormat::Text if self.is_empty() => result.push_str("No changes\n"),
+            DiffFormat::Text => {
+                for key in &self.added {
+                    result.push_str(&format!("added: {}\n", key));
+                }
+                for key in &self.removed {
+                    result.push_str(&format!("removed: {}\n", key));
+                }
+                for renamed in &self.renamed {
+                    result.push_str(&format!("renamed: {} -> {}\n", renamed.from, renamed.to));
+                }
+                for changes in &self.changed {
+                    result.push_str(&format!("changed: {}\n", changes.key));
+                    if let Some(comment) = &changes.comment {
+                        let comment = describe(&comment.old, &comment.new);
+                        result.push_str(&format!("  comment: {}\n", comment));
+                    }
+                    for value in &changes.values {
+                        let quantity = match &value.quantity {
+                            Some(quantity) => format!(":{}", quantity),
+                            None => String::new(),
+                        };
+                        result.push_str(&format!(
+                            "  {}{}: {}\n",
+                            value.locale,
+                            quantity,
+                            describe(&value.old, &value.new)
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl std::fmt::Display for KeyName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.plural {
+            true => write!(f, "{} (plural)", self.name),
+            false => write!(f, "{}", self.name),
+        }
+    }
+}
+
+fn describe(old: &Option<String>, new: &Option<String>) -> String {
+    match (old, new) {
+        (Some(old), Some(new)) => format!("{:?} -> {:?}", old, new),
+        (None, Some(new)) => format!("added {:?}", new),
+        (Some(old), None) => format!("removed {:?}", old),
+        (None, None) => String::new(),
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn source(name: &str, text: &str) -> Source {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join(format!("{}.txt", name));
+    std::fs::write(&path, text).unwrap();
+    Source {
+        name: name.to_string(),
+        file: crate::parse::parse(&path).unwrap(),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn finds_added_removed_renamed_and_changed_keys() -> Result<()> {
+    let old = [source(
+        "strings",
+        "[[Main]]
+  [title]
+    comment = Screen title
+    en = Hello
+    ru = Привет
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+    ru:other = %d песен
+  [cancel]
+    en = Cancel
+  [gone]
+    en = Gone
+",
+    )];
+    let new = [
+        source(
+            "strings",
+            "[[Main]]
+  [title]
+    en = Hello!
+    de = Hallo
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+    ru:few = %d песни
+    ru:other = %d песен
+",
+        ),
+        // Keys moved to another source are unchanged
+        source(
+            "buttons",
+            "[[Buttons]]
+  [button_cancel]
+    en = Cancel
+  [songs]
+    en = Songs
+",
+        ),
+    ];
+    let diff = diff(&old, &new);
+    let expected = "added: songs
+removed: gone
+renamed: cancel -> button_cancel
+changed: title
+  comment: removed \"Screen title\"
+  en: \"Hello\" -> \"Hello!\"
+  ru: removed \"Привет\"
+  de: added \"Hallo\"
+changed: songs (plural)
+  ru:few: added \"%d песни\"
+";
+    assert_eq!(diff.render(DiffFormat::Text)?, expected);
+    assert!(!diff.is_empty());
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Escaping of raw (see `parse::unescape`) text for string literals of output formats.
+//!
+//! Quotes around literals are not added, that is up to the caller.
+
+/// Text content or attribute value of an XML element
+pub fn xml(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// JSON string
+pub fn json(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Value of an iOS `.strings` entry
+pub fn strings(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Kotlin string literal, `$` is escaped so it doesn't start a template
+pub fn kotlin(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '$' => result.push_str("\\$"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Swift string literal
+pub fn swift(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\0' => result.push_str("\\0"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn escapes_xml() {
+    assert_eq!(
+        xml("<b>Tom's</b> & \"Jerry\""),
+        "&lt;b&gt;Tom&apos;s&lt;/b&gt; &amp; &quot;Jerry&quot;"
+    );
+}
+
+#[test]
+fn escapes_json() {
+    assert_eq!(json("\"a\"\\b\n\u{1}"), "\\\"a\\\"\\\\b\\n\\u0001");
+}
+
+#[test]
+fn escapes_string_literals() {
+    let text = "Pay $5 \"now\"\\\n";
+    assert_eq!(strings(text), "Pay $5 \\\"now\\\"\\\\\\n");
+    assert_eq!(kotlin(text), "Pay \\$5 \\\"now\\\"\\\\\\n");
+    assert_eq!(swift(text), "Pay $5 \\\"now\\\"\\\\\\n");
+}
@@ -0,0 +1,509 @@
+//! Exchange of strings with translators: texts which are exported for a locale
+//! and merging of translations back into twine sources.
+
+use anyhow::{anyhow, Ok, Result};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::output::OutputFiles;
+use crate::parse::{self, File, Key, StringValue};
+use crate::pipeline::Source;
+use crate::plural;
+use crate::twine::{self, TwineDocument};
+
+/// Key exported for translation into some locale
+pub struct ExportEntry<'a> {
+    pub key: &'a Key,
+    /// A single text, or one per plural quantity of the target locale in CLDR order
+    pub texts: Vec<ExportText>,
+}
+
+pub struct ExportText {
+    /// Plural quantity, `None` for single strings
+    pub quantity: Option<String>,
+    /// Text in the default language
+    pub source: String,
+    /// Current translation, if any
+    pub target: Option<String>,
+}
+
+/// Locales to export: `locales` if any are given, otherwise all locales of `sources`
+/// but `default_lang`
+pub fn target_locales(
+    sources: &[Source],
+    default_lang: &str,
+    locales: &[String],
+) -> Result<Vec<String>> {
+    let mut targets: BTreeSet<&str> = sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys)
+        .flat_map(|key| &key.localizations)
+        .map(|str| str.language_code.as_str())
+        .collect();
+    if !targets.remove(default_lang) {
+        return Err(anyhow!(
+            "No strings in the default language \"{}\"",
+            default_lang
+        ));
+    }
+    if !locales.is_empty() {
+        return Ok(locales.to_vec());
+    }
+    Ok(targets.into_iter().map(str::to_string).collect())
+}
+
+/// Keys of `file` which have a text in `default_lang`, prepared for translation into `locale`.
+///
+/// Plurals get the quantities used by the locale, the default language text of a
+/// missing quantity is taken from "other". Texts are unescaped.
+pub fn export_entries<'a>(
+    file: &'a File,
+    default_lang: &str,
+    locale: &str,
+) -> Vec<ExportEntry<'a>> {
+    file.sections
+        .iter()
+        .flat_map(|section| &section.keys)
+        .filter_map(|key| {
+            let source = key
+                .localizations
+                .iter()
+                .find(|str| str.language_code == default_lang)?;
+            let texts = match &source.value {
+                StringValue::Single(_) => vec![ExportText {
+                    quantity: None,
+                    source: key_text(key, default_lang, &None)?,
+                    target: key_text(key, locale, &None),
+                }],
+                StringValue::Plural { quantities } => {
+                    let quantities: Vec<&str> =
+                        quantities.iter().map(|q| q.quantity.as_str()).collect();
+                    target_quantities(key, &quantities, locale)
+                        .into_iter()
+                        .map(|quantity| {
+                            let quantity = Some(quantity);
+                            let source = key_text(key, default_lang, &quantity)
+                                .or_else(|| key_text(key, default_lang, &Some("other".to_string())))
+                                .unwrap_or_default();
+                            ExportText {
+                                target: key_text(key, locale, &quantity),
+                                quantity,
+                                source,
+                            }
+                        })
+                        .collect()
+                }
+            };
+            Some(ExportEntry { key, texts })
+        })
+        .collect()
+}
+
+/// CLDR quantities of the locale, or the ones of the default language and the existing
+/// translation if the locale isn't known
+fn target_quantities(key: &Key, default_quantities: &[&str], locale: &str) -> Vec<String> {
+    if let Some(categories) = plural::categories(locale) {
+        return categories.iter().map(|c| c.to_string()).collect();
+    }
+    let mut quantities: Vec<String> = default_quantities.iter().map(|q| q.to_string()).collect();
+    let translated = key
+        .localizations
+        .iter()
+        .find(|str| str.language_code == locale);
+    if let Some(StringValue::Plural {
+        quantities: translated,
+    }) = translated.map(|str| &str.value)
+    {
+        quantities.extend(translated.iter().map(|q| q.quantity.clone()));
+    }
+    quantities.sort_by_key(|quantity| plural::order(quantity));
+    quantities.dedup();
+    quantities
+}
+
+/// Text of the default language a translation was made from
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SourceText {
+    pub locale: String,
+    pub text: String,
+}
+
+/// Translated text of a key read from a translators' file
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Translation {
+    pub key: String,
+    /// Plural quantity, `None` for single strings
+    pub quantity: Option<String>,
+    pub locale: String,
+    /// Set if the file keeps the text which was translated, it's checked to be still the same
+    pub source: Option<SourceText>,
+    /// Text as written in twine files, i.e. not escaped for Android
+    pub text: String,
+    /// Where the translation was read, e.g. "ru.xlf: greeting"
+    pub origin: String,
+}
+
+/// Translation which was not merged
+#[derive(PartialEq, Eq, Debug)]
+pub struct Conflict {
+    pub origin: String,
+    pub reason: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct ImportReport {
+    pub updated: usize,
+    /// Keys which didn't exist and were added
+    pub created: usize,
+    pub unchanged: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ImportReport {
+    /// Summary line with conflicts listed below it
+    pub fn describe(&self) -> String {
+        let mut result = format!("{} translations updated", self.updated);
+        if self.created > 0 {
+            result.push_str(&format!(", {} keys created", self.created));
+        }
+        result.push_str(&format!(
+            ", {} unchanged, {} conflicts",
+            self.unchanged,
+            self.conflicts.len()
+        ));
+        for conflict in &self.conflicts {
+            result.push_str(&format!("\n  {}: {}", conflict.origin, conflict.reason));
+        }
+        result
+    }
+}
+
+struct Target {
+    path: PathBuf,
+    file: File,
+    document: TwineDocument,
+    changed: bool,
+}
+
+/// Twine files being updated by an import. They are parsed to look keys up by name
+/// and edited as documents to keep their layout.
+pub struct TwineFiles {
+    targets: Vec<Target>,
+}
+
+impl TwineFiles {
+    pub fn read(paths: &[PathBuf]) -> Result<TwineFiles> {
+        let targets = paths
+            .iter()
+            .map(|path| {
+                Ok(Target {
+                    path: path.clone(),
+                    file: parse::parse(path)
+                        .map_err(|err| anyhow!("Failed to parse {}: {}", path.display(), err))?,
+                    document: TwineDocument::read(path)?,
+                    changed: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(TwineFiles { targets })
+    }
+
+    /// Key named `name` as it was read, a key may be both single and plural
+    pub fn find_key(&self, name: &str, is_plural: bool) -> Option<&Key> {
+        self.targets
+            .iter()
+            .find_map(|target| find_key(&target.file, name, is_plural))
+    }
+
+    /// Sets `attribute` of the key found with `find_key`, returns false if there is no such key
+    pub fn set(&mut self, name: &str, is_plural: bool, attribute: &str, value: &str) -> bool {
+        let Some(target) = self
+            .targets
+            .iter_mut()
+            .find(|target| find_key(&target.file, name, is_plural).is_some())
+        else {
+            return false;
+        };
+        target.document.set(name, attribute, value);
+        target.changed = true;
+        true
+    }
+
+    /// Appends a new key to the first file
+    pub fn add_key(&mut self, name: &str, attributes: &[(String, String)]) -> Result<()> {
+        let target = self
+            .targets
+            .first_mut()
+            .ok_or_else(|| anyhow!("No twine files to add \"{}\" to", name))?;
+        target.document.add_key(name, attributes);
+        target.changed = true;
+        Ok(())
+    }
+
+    /// Contents of the files which were changed
+    pub fn into_changed(self) -> OutputFiles {
+        self.targets
+            .into_iter()
+            .filter(|target| target.changed)
+            .map(|target| (target.path, target.document.to_string()))
+            .collect()
+    }
+
+    /// Returns true if the translation changed a file, the reason of a conflict otherwise
+    fn merge(&mut self, translation: &Translation) -> Result<bool, String> {
+        let is_plural = translation.quantity.is_some();
+        let Some(key) = self.find_key(&translation.key, is_plural) else {
+            return Err(match is_plural {
+                true => format!("no plural key \"{}\"", translation.key),
+                false => format!("no key \"{}\"", translation.key),
+            });
+        };
+        let current = key_text(key, &translation.locale, &translation.quantity);
+
+        if let Some(source) = &translation.source {
+            let current_source = key_text(key, &source.locale, &translation.quantity)
+                .or_else(|| key_text(key, &source.locale, &Some("other".to_string())));
+            if current_source.as_deref() != Some(source.text.as_str()) {
+                return Err(format!(
+                    "{} text changed since export, was \"{}\", now \"{}\"",
+                    source.locale,
+                    source.text,
+                    current_source.unwrap_or_default()
+                ));
+            }
+        }
+
+        if current.as_deref() == Some(translation.text.as_str()) {
+            return Result::Ok(false);
+        }
+        let attribute = match &translation.quantity {
+            Some(quantity) => format!("{}:{}", translation.locale, quantity),
+            None => translation.locale.clone(),
+        };
+        self.set(&translation.key, is_plural, &attribute, &translation.text);
+        Result::Ok(true)
+    }
+}
+
+/// Merges `translations` into the twine files at `paths`, keys are looked up by name in all of them.
+///
+/// Translations whose key is unknown, whose kind (single or plural) differs or whose source
+/// text changed since they were exported are not merged but reported as conflicts.
+/// Returns the changed files.
+pub fn merge(
+    paths: &[PathBuf],
+    translations: &[Translation],
+) -> Result<(OutputFiles, ImportReport)> {
+    let mut files = TwineFiles::read(paths)?;
+    let mut report = ImportReport::default();
+    for translation in translations {
+        match files.merge(translation) {
+            Result::Ok(true) => report.updated += 1,
+            Result::Ok(false) => report.unchanged += 1,
+            Err(reason) => report.conflicts.push(Conflict {
+                origin: translation.origin.clone(),
+                reason,
+            }),
+        }
+    }
+    Ok((files.into_changed(), report))
+}
+
+/// Merges keys read from another platform's strings into the twine files at `paths`.
+///
+/// Unknown keys are added to the first file and texts missing in the twine files are set.
+/// Texts which differ from the twine ones are kept as they are and reported as conflicts,
+/// `platform` names where the keys were read, e.g. "iOS".
+pub fn merge_keys(
+    paths: &[PathBuf],
+    keys: &[Key],
+    platform: &str,
+) -> Result<(OutputFiles, ImportReport)> {
+    let mut files = TwineFiles::read(paths)?;
+    let mut report = ImportReport::default();
+    for key in keys {
+        let origin = format!("{}: {}", platform, key.name);
+        let is_plural = key
+            .localizations
+            .iter()
+            .any(|str| matches!(str.value, StringValue::Plural { .. }));
+        let Some(existing) = files.find_key(&key.name, is_plural) else {
+            let reason = match files.find_key(&key.name, !is_plural) {
+                Some(_) if is_plural => "is a single key in the twine files",
+                Some(_) => "is a plural key in the twine files",
+                None => {
+                    files.add_key(&key.name, &twine::key_attributes(key))?;
+                    report.created += 1;
+                    continue;
+                }
+            };
+            report.conflicts.push(Conflict {
+                origin,
+                reason: reason.to_string(),
+            });
+            continue;
+        };
+
+        let mut changes: Vec<(String, String)> = Vec::new();
+        if let (None, Some(comment)) = (&existing.comment, &key.comment) {
+            changes.push(("comment".to_string(), comment.clone()));
+        }
+        for str in &key.localizations {
+            let texts: Vec<(Option<String>, &str)> = match &str.value {
+                StringValue::Plural { quantities } => quantities
+                    .iter()
+                    .map(|plural| (Some(plural.quantity.clone()), plural.text.as_str()))
+                    .collect(),
+                StringValue::Single(text) if !is_plural => vec![(None, text)],
+                StringValue::Single(_) => {
+                    report.conflicts.push(Conflict {
+                        origin: origin.clone(),
+                        reason: format!(
+                            "{} is a single text while others are plurals",
+                            str.language_code
+                        ),
+                    });
+                    continue;
+                }
+            };
+            for (quantity, text) in texts {
+                let text = parse::unescape(text);
+                let attribute = match &quantity {
+                    Some(quantity) => format!("{}:{}", str.language_code, quantity),
+                    None => str.language_code.clone(),
+                };
+                match key_text(existing, &str.language_code, &quantity) {
+                    None => {
+                        changes.push((attribute, text));
+                        report.updated += 1;
+                    }
+                    Some(current) if same_text(&current, &text) => report.unchanged += 1,
+                    Some(current) => report.conflicts.push(Conflict {
+                        origin: origin.clone(),
+                        reason: format!(
+                            "{} differs, twine files have \"{}\", {} has \"{}\"",
+                            attribute, current, platform, text
+                        ),
+                    }),
+                }
+            }
+        }
+        for (attribute, value) in changes {
+            files.set(&key.name, is_plural, &attribute, &value);
+        }
+    }
+    Ok((files.into_changed(), report))
+}
+
+/// Texts are the same if they only differ in how placeholders are numbered, e.g. "%s" and "%1$s"
+fn same_text(a: &str, b: &str) -> bool {
+    let numbered = |text: &str| {
+        parse::replace_placeholders(text, "%%", |placeholder| {
+            format!(
+                "%{}${}{}",
+                placeholder.position,
+                placeholder.modifiers(),
+                placeholder.conversion
+            )
+        })
+    };
+    a == b || numbered(a) == numbered(b)
+}
+
+fn find_key<'a>(file: &'a File, name: &str, is_plural: bool) -> Option<&'a Key> {
+    file.sections
+        .iter()
+        .flat_map(|section| &section.keys)
+        .filter(|key| key.name == name)
+        .find(|key| {
+            key.localizations
+                .iter()
+                .all(|str| matches!(str.value, StringValue::Plural { .. }) == is_plural)
+        })
+}
+
+/// Unescaped text of `key` in `locale`, like it is exported to translators
+pub fn key_text(key: &Key, locale: &str, quantity: &Option<String>) -> Option<String> {
+    let value = &key
+        .localizations
+        .iter()
+        .find(|str| str.language_code == locale)?
+        .value;
+    match (value, quantity) {
+        (StringValue::Single(text), None) => Some(parse::unescape(text)),
+        (StringValue::Plural { quantities }, Some(quantity)) => quantities
+            .iter()
+            .find(|plural| plural.quantity == *quantity)
+            .map(|plural| parse::unescape(&plural.text)),
+        _ => None,
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use assert_fs::prelude::*;
+
+/// Source named "strings" parsed from twine `text`, shared by the tests of exchange formats
+#[cfg(test)]
+pub(crate) fn source(text: &str) -> Source {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let twine = dir.child("strings.txt");
+    twine.write_str(text).unwrap();
+    Source {
+        name: "strings".to_string(),
+        file: parse::parse(twine.path()).unwrap(),
+    }
+}
+
+#[cfg(test)]
+fn translation(key: &str, quantity: Option<&str>, source: Option<&str>, text: &str) -> Translation {
+    Translation {
+        key: key.to_string(),
+        quantity: quantity.map(str::to_string),
+        locale: "ru".to_string(),
+        source: source.map(|text| SourceText {
+            locale: "en".to_string(),
+            text: text.to_string(),
+        }),
+        text: text.to_string(),
+        origin: format!("test: {}", key),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn merges_translations_and_reports_conflicts() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let twine = dir.child("strings.txt");
+    twine.write_str(
+        "[[Main]]\n  [find]\n    en = Find\n    ru = Искать\n  [close]\n    en = Close\n    ru = Закрыть\n  [songs]\n    en:one = %d song\n    en:other = %d songs\n",
+    )?;
+    let translations = vec![
+        translation("find", None, Some("Find"), "Найти"),
+        translation("close", None, Some("Close"), "Закрыть"),
+        translation("songs", Some("few"), Some("%d songs"), "%d песни"),
+        translation("find", None, Some("Search"), "Поиск"),
+        translation("open", None, None, "Открыть"),
+        translation("find", Some("one"), None, "Найти"),
+    ];
+
+    let (files, report) = merge(&[twine.to_path_buf()], &translations)?;
+
+    assert_eq!(report.updated, 2);
+    assert_eq!(report.unchanged, 1);
+    let reasons: Vec<&str> = report.conflicts.iter().map(|c| c.reason.as_str()).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            "en text changed since export, was \"Search\", now \"Find\"",
+            "no key \"open\"",
+            "no plural key \"find\"",
+        ]
+    );
+    assert_eq!(
+        files[twine.path()],
+        "[[Main]]\n  [find]\n    en = Find\n    ru = Найти\n  [close]\n    en = Close\n    ru = Закрыть\n  [songs]\n    en:one = %d song\n    en:other = %d songs\n    ru:few = %d песни\n"
+    );
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use anyhow::{anyhow, Ok, Result};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::output::OutputFiles;
+use crate::parse::{File, Key, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Output backend which renders parsed sources into files.
+///
+/// Generators only render, writing is done by the pipeline, so every backend
+/// gets the same output handling.
+pub trait Generator: Send + Sync {
+    /// Name used to select the generator, e.g. "android"
+    fn name(&self) -> &str;
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles>;
+
+    /// Whether output files are named after the sources, the pipeline then
+    /// rejects sources with the same name
+    fn file_per_source(&self) -> bool {
+        false
+    }
+}
+
+/// Generators available for a run, looked up by name
+#[derive(Default)]
+pub struct Registry {
+    generators: Vec<Box<dyn Generator>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Registry with all generators built into utas
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(Box::new(crate::android_gen::AndroidGenerator));
+        registry.register(Box::new(crate::kotlin_gen::KotlinGenerator::default()));
+        registry.register(Box::new(crate::ios_gen::IosGenerator));
+        registry.register(Box::new(crate::swift_gen::SwiftGenerator));
+        registry.register(Box::new(crate::xcstrings_gen::XcstringsGenerator));
+        registry.register(Box::new(crate::compose_gen::ComposeGenerator));
+        registry.register(Box::new(crate::arb_gen::ArbGenerator));
+        registry.register(Box::new(crate::web_gen::WebGenerator::default()));
+        registry.register(Box::new(crate::ts_gen::TypesGenerator::default()));
+        registry
+    }
+
+    /// Adds a generator, replacing an already registered one with the same name
+    pub fn register(&mut self, generator: Box<dyn Generator>) {
+        self.generators.retain(|g| g.name() != generator.name());
+        self.generators.push(generator);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Generator> {
+        self.generators
+            .iter()
+            .find(|g| g.name() == name)
+            .map(|g| g.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.generators.iter().map(|g| g.name()).collect()
+    }
+}
+
+/// Value of `key` in `default_lang`, or in its first locale. Accessor generators
+/// take the types of the arguments from it.
+pub fn typed_value<'a>(key: &'a Key, default_lang: &Option<String>) -> Option<&'a StringValue> {
+    key.localizations
+        .iter()
+        .find(|str| Some(&str.language_code) == default_lang.as_ref())
+        .or(key.localizations.first())
+        .map(|str| &str.value)
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Clone)]
+pub struct Locale {
+    pub value: String,
+}
+
+/// String of a key in some locale, borrowed from the parsed sources
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Line<'a> {
+    pub name: &'a str,
+    pub comment: Option<&'a str>,
+    pub tags: &'a [String],
+    pub value: &'a StringValue,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct StrLines<'a> {
+    pub value: Vec<Line<'a>>,
+}
+
+/// Strings of parsed sources grouped by locale, locales are ordered by their code
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct GenResult<'a> {
+    pub value: BTreeMap<Locale, StrLines<'a>>,
+}
+
+impl<'a> GenResult<'a> {
+    /// Groups keys of `sources` by locale, keeping the order of the keys in the sources
+    pub fn from_files(sources: impl IntoIterator<Item = &'a File>) -> Result<GenResult<'a>> {
+        let mut result: BTreeMap<Locale, StrLines<'a>> = BTreeMap::new();
+        for source in sources {
+            if source.sections.is_empty() {
+                return Err(anyhow!("Expected at least one section"));
+            };
+
+            for key in source.sections.iter().flat_map(|section| &section.keys) {
+                for str in &key.localizations {
+                    let code = Locale {
+                        value: str.language_code.clone(),
+                    };
+                    result.entry(code).or_default().value.push(Line {
+                        name: &key.name,
+                        comment: key.comment.as_deref(),
+                        tags: &key.tags,
+                        value: &str.value,
+                    })
+                }
+            }
+        }
+        Ok(GenResult { value: result })
+    }
+
+    /// Adds strings of `default_lang` to locales which don't have them, nothing is added
+    /// if the sources have no strings in `default_lang`
+    pub fn fill_absent_translations(&mut self, default_lang: &Option<String>) {
+        let Some(lang) = default_lang else {
+            return;
+        };
+        let Some(default_strings) = self
+            .value
+            .get(&Locale {
+                value: lang.clone(),
+            })
+            .cloned()
+        else {
+            return;
+        };
+        for (locale, lines) in self.value.iter_mut() {
+            if locale.value != *lang {
+                let names_for_locale: HashSet<&str> = lines.value.iter().map(|l| l.name).collect();
+                // Appended in the order of the default locale to keep the output stable
+                let difference: Vec<Line> = default_strings
+                    .value
+                    .iter()
+                    .filter(|line| !names_for_locale.contains(line.name))
+                    .copied()
+                    .collect();
+                lines.value.extend(difference);
+            }
+        }
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{LocalizedString, Section};
+
+#[cfg(test)]
+fn plain_str(lang: &str, txt: &str) -> LocalizedString {
+    LocalizedString {
+        language_code: lang.to_string(),
+        value: StringValue::Single(txt.to_string()),
+    }
+}
+
+#[cfg(test)]
+fn key(name: &str, localizations: Vec<LocalizedString>) -> Key {
+    Key {
+        name: name.to_string(),
+        comment: None,
+        tags: Vec::new(),
+        localizations,
+    }
+}
+
+#[cfg(test)]
+fn file(keys: Vec<Key>) -> File {
+    File {
+        sections: vec![Section { name: None, keys }],
+    }
+}
+
+#[cfg(test)]
+fn names(result: &GenResult, lang: &str) -> Vec<String> {
+    result.value[&Locale {
+        value: lang.to_string(),
+    }]
+    .value
+    .iter()
+    .map(|line| line.name.to_string())
+    .collect()
+}
+
+#[cfg(test)]
+struct FakeGenerator(&'static str);
+
+#[cfg(test)]
+impl Generator for FakeGenerator {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn render(&self, _sources: &[Source], _options: &GenOptions) -> Result<OutputFiles> {
+        Ok(OutputFiles::new())
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn groups_keys_of_several_files_by_locale() -> Result<()> {
+    let first = file(vec![
+        key("find", vec![plain_str("ru", "Найти"), plain_str("en", "Find")]),
+        key("search", vec![plain_str("mn", "Хайх")]),
+    ]);
+    let second = file(vec![key("close", vec![plain_str("en", "Close")])]);
+
+    let result = GenResult::from_files([&first, &second])?;
+
+    let locales: Vec<&str> = result.value.keys().map(|l| l.value.as_str()).collect();
+    assert_eq!(locales, vec!["en", "mn", "ru"]);
+    assert_eq!(names(&result, "en"), vec!["find", "close"]);
+    assert_eq!(names(&result, "mn"), vec!["search"]);
+    assert_eq!(names(&result, "ru"), vec!["find"]);
+    Ok(())
+}
+
+#[test]
+fn borrows_values_from_sources() -> Result<()> {
+    let source = file(vec![key("find", vec![plain_str("en", "Find")])]);
+    let result = GenResult::from_files([&source])?;
+    let line = result.value[&Locale {
+        value: "en".to_string(),
+    }]
+    .value[0];
+    assert!(std::ptr::eq(
+        line.value,
+        &source.sections[0].keys[0].localizations[0].value
+    ));
+    Ok(())
+}
+
+#[test]
+fn fills_absent_translations_from_default_lang() -> Result<()> {
+    let source = file(vec![
+        key("first", vec![plain_str("en", "First"), plain_str("ru", "Первый")]),
+        key("second", vec![plain_str("en", "Second")]),
+        key("third", vec![plain_str("ru", "Третий")]),
+    ]);
+    let mut result = GenResult::from_files([&source])?;
+    result.fill_absent_translations(&Some("en".to_string()));
+    assert_eq!(names(&result, "en"), vec!["first", "second"]);
+    assert_eq!(names(&result, "ru"), vec!["first", "third", "second"]);
+    Ok(())
+}
+
+#[test]
+fn groups_keys_of_all_sections() -> Result<()> {
+    let mut source = file(vec![key("find", vec![plain_str("en", "Find")])]);
+    source.sections.push(Section {
+        name: Some("Settings".to_string()),
+        keys: vec![key("title", vec![plain_str("en", "Settings")])],
+    });
+    let result = GenResult::from_files([&source])?;
+    assert_eq!(names(&result, "en"), vec!["find", "title"]);
+    Ok(())
+}
+
+#[test]
+fn skips_filling_without_default_lang_strings() -> Result<()> {
+    let source = file(vec![key("find", vec![plain_str("ru", "Найти")])]);
+    let mut result = GenResult::from_files([&source])?;
+    result.fill_absent_translations(&Some("en".to_string()));
+    assert_eq!(names(&result, "ru"), vec!["find"]);
+    Ok(())
+}
+
+#[test]
+fn registry_finds_generators_by_name() {
+    let mut registry = Registry::builtin();
+    assert_eq!(registry.names(), vec!["android", "kotlin", "ios", "swift", "xcstrings", "compose", "arb", "web", "typescript"]);
+
+    registry.register(Box::new(FakeGenerator("custom")));
+    registry.register(Box::new(FakeGenerator("android")));
+    assert_eq!(registry.names(), vec!["kotlin", "ios", "swift", "xcstrings", "compose", "arb", "web", "typescript", "custom", "android"]);
+    assert!(registry.get("custom").is_some());
+    assert!(registry.get("unknown").is_none());
+}
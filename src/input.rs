@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Ok, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Files created by operating systems and file managers that are never twine sources
+const SYSTEM_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Decides which files found while walking input directories are twine sources
+pub struct InputFilter {
+    recursive: bool,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl InputFilter {
+    pub fn new(recursive: bool, include: &[String], exclude: &[String]) -> Result<InputFilter> {
+        Ok(InputFilter {
+            recursive,
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    /// `relative_path` is the path of a file relative to the input directory it was found in
+    fn accepts(&self, relative_path: &Path) -> bool {
+        let included = match &self.include {
+            Some(include) => include.is_match(relative_path),
+            None => true,
+        };
+        let excluded = match &self.exclude {
+            Some(exclude) => exclude.is_match(relative_path),
+            None => false,
+        };
+        included && !excluded
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob =
+            Glob::new(pattern).map_err(|err| anyhow!("Invalid glob \"{}\": {}", pattern, err))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Collects twine source files from `inputs`.
+///
+/// Directories are walked (recursively if the filter says so) and only files accepted by
+/// the filter are taken, explicitly passed files are always taken. Hidden and system files
+/// found in directories are skipped. The result is sorted, so the order doesn't depend on
+/// the file system.
+pub fn collect_sources(inputs: &[PathBuf], filter: &InputFilter) -> Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            collect_from_dir(input, input, filter, &mut result)?;
+        } else if input.is_file() {
+            result.push(input.clone());
+        } else {
+            return Err(anyhow!("Input {} does not exist", input.display()));
+        }
+    }
+    result.sort();
+    result.dedup();
+    Ok(result)
+}
+
+fn collect_from_dir(
+    root: &Path,
+    dir: &Path,
+    filter: &InputFilter,
+    result: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        if is_hidden_or_system(&item.file_name().to_string_lossy()) {
+            continue;
+        }
+        let path = item.path();
+        let file_type = item.file_type()?;
+        if file_type.is_dir() {
+            if filter.recursive {
+                collect_from_dir(root, &path, filter, result)?;
+            }
+        } else if file_type.is_file() && filter.accepts(path.strip_prefix(root)?) {
+            result.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_hidden_or_system(file_name: &str) -> bool {
+    file_name.starts_with('.') || SYSTEM_FILES.contains(&file_name)
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use assert_fs::prelude::*;
+
+#[cfg(test)]
+fn file_names(root: &Path, paths: Vec<PathBuf>) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect()
+}
+
+#[cfg(test)]
+fn sample_dir() -> Result<assert_fs::TempDir> {
+    let dir = assert_fs::TempDir::new()?;
+    dir.child("common.txt").touch()?;
+    dir.child(".DS_Store").touch()?;
+    dir.child(".hidden.twine").touch()?;
+    dir.child("Thumbs.db").touch()?;
+    dir.child("feature/login.twine").touch()?;
+    dir.child("feature/deep/profile.twine").touch()?;
+    dir.child("feature/notes.md").touch()?;
+    dir.child(".git/config").touch()?;
+    Ok(dir)
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn collects_top_level_files_skipping_system_ones() -> Result<()> {
+    let dir = sample_dir()?;
+    let filter = InputFilter::new(false, &[], &[])?;
+    let sources = collect_sources(&[dir.to_path_buf()], &filter)?;
+    assert_eq!(file_names(dir.path(), sources), vec!["common.txt"]);
+    Ok(())
+}
+
+#[test]
+fn collects_nested_files_when_recursive() -> Result<()> {
+    let dir = sample_dir()?;
+    let filter = InputFilter::new(true, &[], &[])?;
+    let sources = collect_sources(&[dir.to_path_buf()], &filter)?;
+    assert_eq!(
+        file_names(dir.path(), sources),
+        vec![
+            "common.txt",
+            "feature/deep/profile.twine",
+            "feature/login.twine",
+            "feature/notes.md"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn collects_files_matching_include_and_not_exclude_globs() -> Result<()> {
+    let dir = sample_dir()?;
+    let filter = InputFilter::new(
+        true,
+        &["**/*.twine".to_string()],
+        &["**/deep/**".to_string()],
+    )?;
+    let sources = collect_sources(&[dir.to_path_buf()], &filter)?;
+    assert_eq!(file_names(dir.path(), sources), vec!["feature/login.twine"]);
+    Ok(())
+}
+
+#[test]
+fn collects_explicit_files_regardless_of_globs() -> Result<()> {
+    let dir = sample_dir()?;
+    let filter = InputFilter::new(false, &["**/*.twine".to_string()], &[])?;
+    let sources = collect_sources(
+        &[dir.child("feature/notes.md").to_path_buf(), dir.to_path_buf()],
+        &filter,
+    )?;
+    assert_eq!(file_names(dir.path(), sources), vec!["feature/notes.md"]);
+    Ok(())
+}
+
+#[test]
+fn fails_on_missing_input() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let filter = InputFilter::new(false, &[], &[])?;
+    assert!(collect_sources(&[dir.child("missing").to_path_buf()], &filter).is_err());
+    Ok(())
+}
@@ -1,199 +1,108 @@
 use anyhow::{anyhow, Ok, Result};
-use std::{collections::HashMap, collections::HashSet};
-use std::{io::Write, path::Path, borrow::BorrowMut};
-use std::{hash::Hash, hash::Hasher};
-use std::fs;
-
-use crate::parse::{File, Key, LocalizedString, PluralValue, Section, StringValue};
-
-#[derive(PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Clone)]
-pub struct Locale {
-    value: String,
-}
-
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone)]
-pub struct StrLines {
-    value: Vec<Line>,
-}
-
-#[derive(Eq, Debug, PartialOrd, Ord, Clone)]
-pub struct Line {
-    name: String,
-    value: StringValue,
-}
-
-impl Hash for Line {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
+use rayon::prelude::*;
+use std::path::Path;
+
+use crate::generator::{GenResult, Generator, StrLines};
+use crate::output::OutputFiles;
+use crate::parse::{File, PluralValue, StringValue};
+use crate::pipeline::{GenOptions, Source};
+#[cfg(test)]
+use crate::generator::{Line, Locale};
+#[cfg(test)]
+use crate::parse::{Key, LocalizedString, Section};
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+/// Writes `<locale>.lproj/<file name>.strings` and `.stringsdict` with keys of all sources
+pub struct IosGenerator;
+
+impl Generator for IosGenerator {
+    fn name(&self) -> &str {
+        "ios"
     }
-}
 
-impl PartialEq for Line {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let generated = generate(sources.iter().map(|src| &src.file), &options.default_lang)?;
+        Ok(render(&generated, &options.output_dir, table_name(options)))
     }
 }
 
-pub struct GenResult {
-    value: HashMap<Locale, StrLines>,
+/// Name of the strings table, "Localizable" unless set in options
+pub fn table_name(options: &GenOptions) -> &str {
+    options.file_name.as_deref().unwrap_or("Localizable")
 }
 
-impl Default for StrLines {
-    fn default() -> Self {
-        Self { value: Default::default() }
-    }
+pub fn render(generated: &GenResult, dir: &Path, file_name: &str) -> OutputFiles {
+    let rendered: Vec<_> = generated
+        .value
+        .par_iter()
+        .filter(|(locale, _)| locale_code_supported_in_ios(&locale.value))
+        .map(|(locale, lines)| {
+            let subpath = dir.join(format!("{}.lproj", locale.value));
+            let (strings, stringsdict) = render_locale(lines);
+            [
+                (subpath.join(format!("{}.strings", file_name)), strings),
+                (subpath.join(format!("{}.stringsdict", file_name)), stringsdict),
+            ]
+        })
+        .collect();
+    rendered.into_iter().flatten().collect()
 }
 
-impl GenResult {
-    pub fn write(
-        &self,
-        dir: impl AsRef<Path>,
-        file_name: &str,
-    ) -> Result<()> {
-        for (locale, lines) in &self.value {
-            if !locale_code_supported_in_ios(&locale.value) {
-                continue;
-            }
+/// Renders contents of .strings and .stringsdict files for a locale
+fn render_locale(lines: &StrLines) -> (String, String) {
+    let mut non_plurals = String::new();
+    let mut plurals = String::new();
 
-            let subpath = dir.as_ref().join(format!("{}.lproj", locale.value));
-            if !subpath.is_dir() {
-                fs::create_dir(&subpath)?;
-            }
-            let non_plurals_file_path = subpath.join(format!("{}.strings", file_name));
-            let mut non_plurals_file = fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(&non_plurals_file_path)?;
-
-            let plurals_file_path = subpath.join(format!("{}.stringsdict", file_name));
-            let mut plurals_file = fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(&plurals_file_path)?;
-
-            plurals_file.write("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".as_bytes())?;
-            plurals_file.write("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n".as_bytes())?;
-            plurals_file.write("<plist version=\"1.0\">\n".as_bytes())?;
-            plurals_file.write("  <dict>\n".as_bytes())?;
-
-            for line in &lines.value {
-                match &line.value {
-                    StringValue::Single(text) => {
-                        non_plurals_file.write(
-                            format!(
-                                "{}\n", 
-                                vec![generate_str_value(&line.name, text)].join("\n")
-                            ).as_bytes()
-                        )?
-                    },
-                    StringValue::Plural { quantities } => {
-                        plurals_file.write(
-                            format!(
-                                "{}\n",
-                                generate_plural_value(&line.name, quantities).join("\n")
-                            ).as_bytes()
-                        )?
-                    },
-                };
-            }
-            plurals_file.write("  </dict>\n".as_bytes())?;
-            plurals_file.write("</plist>\n".as_bytes())?;
-        }
+    plurals.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    plurals.push_str("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n");
+    plurals.push_str("<plist version=\"1.0\">\n");
+    plurals.push_str("  <dict>\n");
 
-        Ok(())
+    for line in &lines.value {
+        match line.value {
+            StringValue::Single(text) => {
+                non_plurals.push_str(&format!("{}\n", generate_str_value(line.name, text)))
+            }
+            StringValue::Plural { quantities } => plurals.push_str(&format!(
+                "{}\n",
+                generate_plural_value(line.name, quantities).join("\n")
+            )),
+        };
     }
+    plurals.push_str("  </dict>\n");
+    plurals.push_str("</plist>\n");
+    (non_plurals, plurals)
 }
 
-fn locale_code_supported_in_ios(code: &str) -> bool {
-    return true;
+fn locale_code_supported_in_ios(_code: &str) -> bool {
+    true
 }
 
-pub fn generate(sources: Vec<File>, default_lang: &Option<String>) -> Result<GenResult> {
-    let generated_files: Vec<_> = sources.iter().map( |src| {
-        generate_for_file(src)
-    }).collect();
-
-    if generated_files.is_empty() {
+/// Merges keys of all sources, locales missing some keys get them from `default_lang`
+pub fn generate<'a>(
+    sources: impl IntoIterator<Item = &'a File>,
+    default_lang: &Option<String>,
+) -> Result<GenResult<'a>> {
+    let sources: Vec<&File> = sources.into_iter().collect();
+    if sources.is_empty() {
         return Err(anyhow!("Expected at least one successfuly generated file"));
     }
 
-    let mut result: HashMap<Locale, StrLines> = HashMap::new();
-    for generated_file in generated_files {
-        for (locale, lines) in generated_file? {
-            result.entry(locale)
-                .and_modify(|current_lines| current_lines.value.extend(lines.clone().value))
-                .or_insert(lines.clone());
-        }
-    }
-
-    fill_absent_translations(result.borrow_mut(), default_lang);
-
-    Ok(GenResult { value: result })
-}
-
-fn generate_for_file(source: &File) -> Result<HashMap<Locale, StrLines>> {
-    if source.sections.len() > 1 {
-        panic!("Expected only one section currently")
-    };
-
-    let Some(keys) = source.sections.first().map(|section| &section.keys) else {
-        return Err(anyhow!("Expected at least one section"))
-    };
-
-    let mut result: HashMap<Locale, StrLines> = HashMap::new();
-    let keys_len = keys.len();
-    for key in keys {
-        let str_name = &key.name;
-        for str in &key.localizations {
-            let code = Locale {
-                value: str.language_code.clone(),
-            };
-
-            let current = &mut result
-                .entry(code)
-                .or_insert(StrLines {
-                    value: Vec::with_capacity(keys_len),
-                })
-                .value;
-
-            current.push(Line {
-                name: str_name.clone(),
-                value: str.value.clone(),
-            })
-        }
-    }
+    let mut result = GenResult::from_files(sources)?;
+    result.fill_absent_translations(default_lang);
 
     Ok(result)
 }
 
-fn fill_absent_translations(map: &mut HashMap<Locale, StrLines>, default_lang: &Option<String>) {
-    match default_lang {
-        Some(lang) => {
-            let default_strings = map.get(&Locale { value: lang.clone() }).unwrap();
-            let set_with_default_strings: HashSet<Line> = default_strings.value.clone().into_iter().collect();
-            for locale in map.clone().keys() {
-                if locale.value != *lang {
-                    let current_entry = map.get(locale).unwrap();
-                    let set_for_locale: HashSet<Line> = current_entry.value.clone().into_iter().collect();
-                    let difference: HashSet<_> = set_with_default_strings.difference(&set_for_locale).map(|x| x.clone()).collect();
-                    map.entry(locale.clone()).and_modify(|f| f.value.extend(difference));
-                }
-            }
-        }
-        None => return
-    }
-}
-
 fn generate_str_value(str_name: &str, str_value: &str) -> String {
-    String::from(format!(
+    format!(
         "\"{}\" = \"{}\";\n",
         str_name, str_value
-    ))
+    )
 }
 
-fn generate_plural_value(str_name: &String, items: &Vec<PluralValue>) -> Vec<String> {
+fn generate_plural_value(str_name: &str, items: &[PluralValue]) -> Vec<String> {
     let mut result: Vec<String> = Vec::with_capacity(items.len() + 2);
     result.push(format!("    <key>{}</key>", str_name));
 
@@ -220,6 +129,7 @@ fn generate_plural_value(str_name: &String, items: &Vec<PluralValue>) -> Vec<Str
 }
 
 // -----------------------------  test tools ------------------------------
+#[cfg(test)]
 fn plain_str(lang: &str, txt: &str) -> LocalizedString {
     LocalizedString {
         language_code: lang.to_string(),
@@ -227,6 +137,7 @@ fn plain_str(lang: &str, txt: &str) -> LocalizedString {
     }
 }
 
+#[cfg(test)]
 fn plurals(lang: &str, quantities: Vec<PluralValue>) -> LocalizedString {
     LocalizedString {
         language_code: lang.to_string(),
@@ -234,6 +145,7 @@ fn plurals(lang: &str, quantities: Vec<PluralValue>) -> LocalizedString {
     }
 }
 
+#[cfg(test)]
 fn plural_val(quantity: &str, text: &str) -> PluralValue {
     PluralValue {
         quantity: quantity.to_string(),
@@ -241,35 +153,35 @@ fn plural_val(quantity: &str, text: &str) -> PluralValue {
     }
 }
 
+#[cfg(test)]
 fn key(name: &str, localizations: Vec<LocalizedString>) -> Key {
     Key {
         name: name.to_string(),
-        localizations: localizations,
+        comment: None,
+        tags: Vec::new(),
+        localizations,
     }
 }
 
-fn sorted_strings(input: GenResult) -> Vec<(Locale, StrLines)> {
-    let mut result = Vec::with_capacity(input.value.len());
-    let mut keys: Vec<&Locale> = input.value.keys().collect();
-    keys.sort();
-    for key in keys {
-        result.push((key.clone(), input.value.get(&key).unwrap().clone()))
+// Expected lines borrow values just like generated ones do, so the values are leaked
+#[cfg(test)]
+fn single(name: &'static str, text: &str) -> Line<'static> {
+    Line {
+        name,
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Single(text.to_string()))),
     }
-    result
-}
-
-fn single(name: &str, text: &str) -> Line {
-    return Line {
-        name: name.to_string(),
-        value: StringValue::Single(text.to_string()),
-    };
 }
 
-fn plural(name: &str, items: Vec<PluralValue>) -> Line {
-    return Line {
-        name: name.to_string(),
-        value: StringValue::Plural { quantities: items },
-    };
+#[cfg(test)]
+fn plural(name: &'static str, items: Vec<PluralValue>) -> Line<'static> {
+    Line {
+        name,
+        comment: None,
+        tags: &[],
+        value: Box::leak(Box::new(StringValue::Plural { quantities: items })),
+    }
 }
 
 // ------------------------------- tests -----------------------------------
@@ -278,9 +190,9 @@ fn generate_1_lang_1_str() -> Result<()> {
     let localizations_kek = vec![plain_str("ru", "Кек")];
     let keys = vec![key("kek", localizations_kek)];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "ru".to_string(),
         },
@@ -291,8 +203,8 @@ fn generate_1_lang_1_str() -> Result<()> {
 
     let expected = GenResult { value: map };
 
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -305,9 +217,9 @@ fn generate_1_lang_2_str() -> Result<()> {
     let keys = vec![key("kek", localizations_kek), key("lil", localizations_lil)];
 
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "ru".to_string(),
         },
@@ -318,8 +230,8 @@ fn generate_1_lang_2_str() -> Result<()> {
 
     let expected = GenResult { value: map };
 
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -335,17 +247,21 @@ fn generate_3_lang_2_str() -> Result<()> {
     let keys = vec![
         Key {
             name: "find".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_find,
         },
         Key {
             name: "search".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_search,
         },
     ];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([
+    let map = BTreeMap::from([
         (
             Locale {
                 value: "ru".to_string(),
@@ -374,8 +290,8 @@ fn generate_3_lang_2_str() -> Result<()> {
 
     let expected = GenResult { value: map };
 
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -388,9 +304,9 @@ fn generate_1_lang_1_str_2_placeholders() -> Result<()> {
     }];
     let keys = vec![key("add", localizations_add)];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "mn".to_string(),
         },
@@ -401,8 +317,8 @@ fn generate_1_lang_1_str_2_placeholders() -> Result<()> {
 
     let expected = GenResult { value: map };
 
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
 
     Ok(())
 }
@@ -411,7 +327,7 @@ fn generate_1_lang_1_str_2_placeholders() -> Result<()> {
 fn generate_error_if_empty_sections() -> Result<()> {
     let source = File { sections: vec![] };
 
-    let actual = generate(vec![source], &None);
+    let actual = generate([&source], &None);
     assert!(actual.is_err());
 
     Ok(())
@@ -422,12 +338,14 @@ fn generate_1_lang_1_simple_plural() -> Result<()> {
     let localizations_songs = vec![plurals("mn", vec![plural_val("other", "%d дуу")])];
     let keys = vec![Key {
         name: "songs".to_string(),
+        comment: None,
+        tags: Vec::new(),
         localizations: localizations_songs,
     }];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "mn".to_string(),
         },
@@ -442,8 +360,8 @@ fn generate_1_lang_1_simple_plural() -> Result<()> {
         },
     )]);
     let expected = GenResult { value: map };
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
     Ok(())
 }
 
@@ -461,17 +379,21 @@ fn generate_1_lang_1_str_1_plurals() -> Result<()> {
     let keys = vec![
         Key {
             name: "chicken".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_chicken,
         },
         Key {
             name: "cows".to_string(),
+            comment: None,
+            tags: Vec::new(),
             localizations: localizations_cows,
         },
     ];
     let source = File {
-        sections: vec![Section { keys }],
+        sections: vec![Section { name: None, keys }],
     };
-    let map = HashMap::from([(
+    let map = BTreeMap::from([(
         Locale {
             value: "en".to_string(),
         },
@@ -500,8 +422,37 @@ fn generate_1_lang_1_str_1_plurals() -> Result<()> {
     )]);
     let expected = GenResult { value: map };
 
-    let actual = generate(vec![source], &None)?;
-    assert_eq!(sorted_strings(expected), sorted_strings(actual));
+    let actual = generate([&source], &None)?;
+    assert_eq!(expected, actual);
+
+    Ok(())
+}
+
+#[test]
+fn generate_fills_absent_translations_in_default_lang_order() -> Result<()> {
+    let keys = vec![
+        key("first", vec![plain_str("en", "First"), plain_str("ru", "Первый")]),
+        key("second", vec![plain_str("en", "Second")]),
+        key("third", vec![plain_str("en", "Third")]),
+        key("fourth", vec![plain_str("en", "Fourth")]),
+    ];
+    let source = File {
+        sections: vec![Section { name: None, keys }],
+    };
+
+    let actual = generate([&source], &Some("en".to_string()))?;
+    let ru = &actual.value[&Locale {
+        value: "ru".to_string(),
+    }];
+    assert_eq!(
+        ru.value,
+        vec![
+            single("first", "Первый"),
+            single("second", "Second"),
+            single("third", "Third"),
+            single("fourth", "Fourth"),
+        ]
+    );
 
     Ok(())
 }
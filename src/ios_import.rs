@@ -0,0 +1,427 @@
+//! Reads iOS `*.lproj` directories with `.strings` and `.stringsdict` tables into the parsed
+//! model, so existing iOS strings can be merged with twine files.
+
+use anyhow::{anyhow, Ok, Result};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::parse::{self, File, Key, LocalizedString, PluralValue, Section, StringValue};
+use crate::plural;
+
+/// Keys read from the tables along with what couldn't be imported
+pub struct IosImport {
+    pub file: File,
+    pub warnings: Vec<String>,
+}
+
+/// Comment Xcode writes for strings without one
+const NO_COMMENT: &str = "No comment provided by engineer.";
+
+/// Reads `<table>.strings` and `<table>.stringsdict` of every `<locale>.lproj` directory
+/// of `dir`, `Base.lproj` holds `default_lang`. Plurals of a `.stringsdict` win over
+/// a `.strings` text with the same key, like they do on iOS.
+pub fn read(dir: &Path, table: &str, default_lang: &str) -> Result<IosImport> {
+    let mut dirs: Vec<(String, String)> = Vec::new();
+    let mut warnings = Vec::new();
+    let entries =
+        fs::read_dir(dir).map_err(|err| anyhow!("Cannot read {}: {}", dir.display(), err))?;
+    for entry in entries {
+        let dir_name = entry?.file_name().to_string_lossy().to_string();
+        let Some(name) = dir_name.strip_suffix(".lproj") else {
+            continue;
+        };
+        match locale_of_dir(name, default_lang) {
+            Some(locale) => dirs.push((dir_name, locale)),
+            None => warnings.push(format!("skipped {}, it's not a locale", dir_name)),
+        }
+    }
+    // "Base.lproj" goes first, so its strings win over a "<default_lang>.lproj" duplicate
+    dirs.sort_by(|(dir_a, locale_a), (dir_b, locale_b)| {
+        (
+            locale_a != default_lang,
+            locale_a,
+            !dir_a.starts_with("Base"),
+            dir_a,
+        )
+            .cmp(&(
+                locale_b != default_lang,
+                locale_b,
+                !dir_b.starts_with("Base"),
+                dir_b,
+            ))
+    });
+
+    let mut keys: IndexMap<String, Key> = IndexMap::new();
+    for (dir_name, locale) in dirs {
+        for extension in ["stringsdict", "strings"] {
+            let path = dir.join(&dir_name).join(format!("{}.{}", table, extension));
+            if !path.is_file() {
+                continue;
+            }
+            let display = format!("{}/{}.{}", dir_name, table, extension);
+            let bytes = fs::read(&path)
+                .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+            let text = decode(&bytes).map_err(|err| anyhow!("Cannot read {}: {}", display, err))?;
+            let strings = match extension {
+                "strings" => read_strings(&text),
+                _ => read_stringsdict(&text, &mut warnings),
+            }
+            .map_err(|err| anyhow!("Failed to read {}: {}", display, err))?;
+            for (name, comment, value) in strings {
+                add(&mut keys, name, comment, &locale, value);
+            }
+        }
+    }
+    Ok(IosImport {
+        file: File {
+            sections: vec![Section {
+                name: None,
+                keys: keys.into_values().collect(),
+            }],
+        },
+        warnings,
+    })
+}
+
+/// Locale of an `.lproj` directory name, e.g. "pt-BR" for "pt-BR" and "pt_BR".
+/// `None` for names which aren't locales, like the legacy "English".
+fn locale_of_dir(name: &str, default_lang: &str) -> Option<String> {
+    lazy_static! {
+        static ref LOCALE_RE: Regex = Regex::new(r"^[a-z]{2,3}([-_][A-Za-z0-9]{2,8})*$").unwrap();
+    }
+    if name == "Base" {
+        return Some(default_lang.to_string());
+    }
+    LOCALE_RE.is_match(name).then(|| name.replace('_', "-"))
+}
+
+fn add(
+    keys: &mut IndexMap<String, Key>,
+    name: String,
+    comment: Option<String>,
+    locale: &str,
+    value: StringValue,
+) {
+    let key = keys.entry(name.clone()).or_insert_with(|| Key {
+        name,
+        comment: None,
+        tags: Vec::new(),
+        localizations: Vec::new(),
+    });
+    if key.comment.is_none() {
+        key.comment = comment;
+    }
+    if key
+        .localizations
+        .iter()
+        .any(|str| str.language_code == locale)
+    {
+        return;
+    }
+    key.localizations.push(LocalizedString {
+        language_code: locale.to_string(),
+        value,
+    });
+}
+
+/// `.strings` files are either UTF-8 or UTF-16 with a byte order mark
+fn decode(bytes: &[u8]) -> Result<String> {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|err| anyhow!(err))
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => Ok(String::from_utf8(rest.to_vec())?),
+        _ => Ok(String::from_utf8(bytes.to_vec())?),
+    }
+}
+
+type Entry = (String, Option<String>, StringValue);
+
+/// Reads `"key" = "value";` pairs, a `/* */` or `//` comment right before a pair is its comment
+fn read_strings(text: &str) -> Result<Vec<Entry>> {
+    let mut result = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut comment: Option<String> = None;
+    loop {
+        skip_whitespace(&mut chars);
+        let Some(c) = chars.next() else {
+            break;
+        };
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some('*') if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            break;
+                        }
+                        Some(c) => body.push(c),
+                        None => return Err(anyhow!("unterminated comment")),
+                    }
+                }
+                comment = Some(body);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                comment = Some(chars.by_ref().take_while(|c| *c != '\n').skip(1).collect());
+            }
+            '"' => {
+                let name = quoted(&mut chars)?;
+                skip_whitespace(&mut chars);
+                let value = match chars.next() {
+                    // A key alone is its own value
+                    Some(';') => name.clone(),
+                    Some('=') => {
+                        skip_whitespace(&mut chars);
+                        if chars.next() != Some('"') {
+                            return Err(anyhow!("expected a quoted value of \"{}\"", name));
+                        }
+                        let value = quoted(&mut chars)?;
+                        skip_whitespace(&mut chars);
+                        if chars.next() != Some(';') {
+                            return Err(anyhow!("expected ';' after the value of \"{}\"", name));
+                        }
+                        value
+                    }
+                    _ => return Err(anyhow!("expected '=' after \"{}\"", name)),
+                };
+                let comment = comment
+                    .take()
+                    .map(|comment| collapse_whitespace(&comment))
+                    .filter(|comment| !comment.is_empty() && comment != NO_COMMENT);
+                result.push((name, comment, StringValue::Single(parse_text(&value)?)));
+            }
+            c => return Err(anyhow!("unexpected '{}'", c)),
+        }
+    }
+    Ok(result)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Rest of a quoted `.strings` text, as it would be written in a twine file.
+/// `\"` and `\'` become plain characters and `\Uxxxx` the character, `\n`, `\t` and `\\`
+/// are kept as they are, line breaks become `\n`.
+fn quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut result = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(result),
+            Some('\\') => match chars.next() {
+                Some(c @ ('n' | 't' | '\\')) => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                Some('r') => (),
+                Some('U' | 'u') => {
+                    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                    match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        Some(c) => result.push(c),
+                        None => return Err(anyhow!("invalid escape \\U{}", code)),
+                    }
+                }
+                Some(c) => result.push(c),
+                None => return Err(anyhow!("unterminated string")),
+            },
+            Some('\n') => result.push_str("\\n"),
+            Some(c) => result.push(c),
+            None => return Err(anyhow!("unterminated string")),
+        }
+    }
+}
+
+/// Reads plural rules of a `.stringsdict` plist. Only formats with a single plural
+/// variable can be twine plurals, the text around the variable goes to every quantity.
+fn read_stringsdict(text: &str, warnings: &mut Vec<String>) -> Result<Vec<Entry>> {
+    lazy_static! {
+        static ref VARIABLE_RE: Regex = Regex::new(r"%(?:\d+\$)?#@([^@]+)@").unwrap();
+    }
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(text, options)?;
+    let root = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("dict"))
+        .ok_or_else(|| anyhow!("no root <dict>"))?;
+    let mut result = Vec::new();
+    for (name, rules) in dict_entries(root) {
+        let entries = dict_entries(rules);
+        let entry = |name: &str| {
+            entries
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, node)| *node)
+        };
+        let format = entry("NSStringLocalizedFormatKey").and_then(|node| node.text());
+        let variables: Vec<_> = format
+            .map(|format| VARIABLE_RE.captures_iter(format).collect())
+            .unwrap_or_default();
+        let [variable] = variables.as_slice() else {
+            warnings.push(format!(
+                "skipped \"{}\", only plurals with one variable are imported",
+                name
+            ));
+            continue;
+        };
+        let Some(variable_rules) = entry(&variable[1]).map(dict_entries) else {
+            return Err(anyhow!(
+                "no rules of variable \"{}\" of \"{}\"",
+                &variable[1],
+                name
+            ));
+        };
+        let format = format.unwrap_or_default();
+        let mut quantities = variable_rules
+            .iter()
+            .filter(|(quantity, _)| plural::CATEGORIES.contains(&quantity.as_str()))
+            .map(|(quantity, node)| {
+                let text = format.replacen(&variable[0], node.text().unwrap_or_default(), 1);
+                Ok(PluralValue {
+                    quantity: quantity.clone(),
+                    text: parse_text(&text.replace('\n', "\\n"))?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        quantities.sort_by_key(|plural| plural::order(&plural.quantity));
+        result.push((name, None, StringValue::Plural { quantities }));
+    }
+    Ok(result)
+}
+
+/// `<key>` elements of a `<dict>` with the elements following them
+fn dict_entries<'a, 'input>(dict: Node<'a, 'input>) -> Vec<(String, Node<'a, 'input>)> {
+    let elements: Vec<Node> = dict.children().filter(Node::is_element).collect();
+    elements
+        .chunks_exact(2)
+        .filter(|pair| pair[0].has_tag_name("key"))
+        .map(|pair| (pair[0].text().unwrap_or_default().to_string(), pair[1]))
+        .collect()
+}
+
+fn parse_text(raw: &str) -> Result<String> {
+    parse::parse_text(raw).map_err(|err| anyhow!(err))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn render(entries: Vec<Entry>) -> String {
+    let mut keys = IndexMap::new();
+    for (name, comment, value) in entries {
+        add(&mut keys, name, comment, "en", value);
+    }
+    let file = File {
+        sections: vec![Section {
+            name: Some("Strings".to_string()),
+            keys: keys.into_values().collect(),
+        }],
+    };
+    crate::twine::render(&file)
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn maps_directories_to_locales() {
+    let locale = |name| locale_of_dir(name, "en");
+    assert_eq!(locale("Base"), Some("en".to_string()));
+    assert_eq!(locale("ru"), Some("ru".to_string()));
+    assert_eq!(locale("pt-BR"), Some("pt-BR".to_string()));
+    assert_eq!(locale("pt_BR"), Some("pt-BR".to_string()));
+    assert_eq!(locale("zh-Hans"), Some("zh-Hans".to_string()));
+    assert_eq!(locale("English"), None);
+}
+
+#[test]
+fn reads_strings_with_comments_and_escapes() -> Result<()> {
+    let strings = r#"/* Shown on
+   the start screen */
+"greeting" = "Hello, %@ & \"friends\"!\nWelcome";
+
+/* No comment provided by engineer. */
+"caf\U00e9" = "Don't\ttab \\ slash";
+// Key only
+"OK";
+"#;
+    let expected = "[[Strings]]
+  [greeting]
+    comment = Shown on the start screen
+    en = Hello, %s & \"friends\"!\\nWelcome
+
+  [café]
+    en = Don't\\ttab \\\\ slash
+
+  [OK]
+    comment = Key only
+    en = OK
+";
+    assert_eq!(render(read_strings(strings)?), expected);
+    assert!(read_strings("\"broken\" = \"value\"").is_err());
+    Ok(())
+}
+
+#[test]
+fn reads_stringsdict_plurals() -> Result<()> {
+    let plist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>songs</key>
+  <dict>
+    <key>NSStringLocalizedFormatKey</key>
+    <string>You have %#@count@</string>
+    <key>count</key>
+    <dict>
+      <key>NSStringFormatSpecTypeKey</key>
+      <string>NSStringPluralRuleType</string>
+      <key>NSStringFormatValueTypeKey</key>
+      <string>d</string>
+      <key>other</key>
+      <string>%d songs</string>
+      <key>one</key>
+      <string>%d song</string>
+    </dict>
+  </dict>
+  <key>files</key>
+  <dict>
+    <key>NSStringLocalizedFormatKey</key>
+    <string>%#@files@ in %#@folders@</string>
+  </dict>
+</dict>
+</plist>
+"#;
+    let mut warnings = Vec::new();
+    let expected = "[[Strings]]
+  [songs]
+    en:one = You have %d song
+    en:other = You have %d songs
+";
+    assert_eq!(render(read_stringsdict(plist, &mut warnings)?), expected);
+    assert_eq!(
+        warnings,
+        vec!["skipped \"files\", only plurals with one variable are imported"]
+    );
+    Ok(())
+}
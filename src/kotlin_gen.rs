@@ -0,0 +1,332 @@
+use anyhow::{anyhow, Ok, Result};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::generator::{self, Generator};
+use crate::output::OutputFiles;
+use crate::parse::{self, File, Key, Placeholder, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Kotlin keywords which can't be function or parameter names without backticks
+const KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+/// Writes `<Source>.kt` for every source, an object with a function per key returning
+/// its text from the Android resources. Arguments are typed after the placeholders,
+/// so a changed placeholder breaks the build instead of the formatting at runtime.
+#[derive(Default)]
+pub struct KotlinGenerator {
+    /// Package of the generated objects, `R` is imported from it
+    pub package: Option<String>,
+    /// Directory of the Kotlin files, relative to the output directory unless absolute.
+    /// Kept apart from the resources, which the output directory usually is.
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Generator for KotlinGenerator {
+    fn name(&self) -> &str {
+        "kotlin"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let package = self.package.as_deref().ok_or_else(|| {
+            anyhow!("The kotlin platform needs a package, set it with --kotlin-package")
+        })?;
+        let dir = self.output_dir.as_ref().ok_or_else(|| {
+            anyhow!("The kotlin platform needs a directory, set it with --kotlin-output-dir")
+        })?;
+        let dir = options.output_dir.join(dir);
+        Ok(sources
+            .par_iter()
+            .map(|source| {
+                let object = object_name(&source.name);
+                let contents = render(&source.file, package, &object, &options.default_lang);
+                (dir.join(format!("{}.kt", object)), contents)
+            })
+            .collect())
+    }
+
+    fn file_per_source(&self) -> bool {
+        true
+    }
+}
+
+/// Renders a Kotlin file with `object <object>` holding accessors of all keys of `file`
+pub fn render(file: &File, package: &str, object: &str, default_lang: &Option<String>) -> String {
+    let keys: Vec<&Key> = file
+        .sections
+        .iter()
+        .flat_map(|section| &section.keys)
+        .collect();
+    let mut functions = Vec::new();
+    for key in &keys {
+        let Some(value) = generator::typed_value(key, default_lang) else {
+            continue;
+        };
+        let is_plural = matches!(value, StringValue::Plural { .. });
+        // Android keeps single and plural resources apart, Kotlin needs different names
+        let has_single_twin = is_plural
+            && keys.iter().any(|other| {
+                other.name == key.name
+                    && matches!(
+                        generator::typed_value(other, default_lang),
+                        Some(StringValue::Single(_))
+                    )
+            });
+        let name = match has_single_twin {
+            true => format!("{}Plural", function_name(&key.name)),
+            false => function_name(&key.name),
+        };
+        functions.push(render_function(key, value, &escape_name(&name)));
+    }
+
+    let mut result = String::new();
+    result.push_str("// Generated by utas, do not edit\n");
+    result.push_str(&format!("package {}\n\n", package));
+    result.push_str("import android.content.Context\n");
+    result.push_str(&format!("import {}.R\n\n", package));
+    result.push_str(&format!("object {} {{\n", object));
+    result.push_str(&functions.join("\n"));
+    result.push_str("}\n");
+    result
+}
+
+fn render_function(key: &Key, value: &StringValue, name: &str) -> String {
+    let resource = key.name.replace('.', "_");
+    let (texts, call) = match value {
+        StringValue::Single(text) => (
+            vec![text.as_str()],
+            format!("context.getString(R.string.{}", resource),
+        ),
+        StringValue::Plural { quantities } => (
+            quantities
+                .iter()
+                .map(|plural| plural.text.as_str())
+                .collect(),
+            format!(
+                "context.resources.getQuantityString(R.plurals.{}, quantity",
+                resource
+            ),
+        ),
+    };
+    let arguments = arguments(&texts);
+
+    let mut parameters = vec!["context: Context".to_string()];
+    if matches!(value, StringValue::Plural { .. }) {
+        parameters.push("quantity: Int".to_string());
+    }
+    parameters.extend(
+        arguments
+            .iter()
+            .map(|(position, kind)| format!("arg{}: {}", position, kind)),
+    );
+    let mut call_arguments = vec![call];
+    call_arguments.extend(arguments.keys().map(|position| format!("arg{}", position)));
+
+    let mut result = String::new();
+    if let Some(comment) = &key.comment {
+        result.push_str(&format!("    /** {} */\n", comment.replace("*/", "* /")));
+    }
+    result.push_str(&format!(
+        "    fun {}({}): String =\n        {})\n",
+        name,
+        parameters.join(", "),
+        call_arguments.join(", ")
+    ));
+    result
+}
+
+/// Kotlin types of the format arguments by their position, the first placeholder
+/// of a position decides. Positions no placeholder takes are `Any?`.
+fn arguments(texts: &[&str]) -> BTreeMap<usize, &'static str> {
+    let mut result = BTreeMap::new();
+    for text in texts {
+        for placeholder in parse::placeholders(text) {
+            result
+                .entry(placeholder.position)
+                .or_insert_with(|| kotlin_type(&placeholder));
+        }
+    }
+    let last = result.keys().last().copied().unwrap_or(0);
+    for position in 1..last {
+        result.entry(position).or_insert("Any?");
+    }
+    result
+}
+
+fn kotlin_type(placeholder: &Placeholder) -> &'static str {
+    match placeholder.conversion {
+        'd' | 'i' | 'u' | 'x' | 'X' | 'o' if placeholder.modifiers().contains('l') => "Long",
+        'd' | 'i' | 'u' | 'x' | 'X' | 'o' => "Int",
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => "Double",
+        'c' => "Char",
+        's' | 'S' => "String",
+        _ => "Any",
+    }
+}
+
+/// Object name of a source, e.g. "MainScreen" for "main_screen"
+fn object_name(source: &str) -> String {
+    let name: String = words(source).iter().map(|word| capitalize(word)).collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("Strings{}", name),
+        Some(_) => name,
+        None => "Strings".to_string(),
+    }
+}
+
+/// Function name of a key, e.g. "loginScreenTitle" for "login_screen_title"
+fn function_name(key: &str) -> String {
+    let mut name = String::new();
+    for (index, word) in words(key).iter().enumerate() {
+        match index {
+            0 => {
+                let mut chars = word.chars();
+                name.extend(chars.next().map(|c| c.to_ascii_lowercase()));
+                name.push_str(chars.as_str());
+            }
+            _ => name.push_str(&capitalize(word)),
+        }
+    }
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+pub(crate) fn words(name: &str) -> Vec<&str> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+pub(crate) fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn escape_name(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("`{}`", name),
+        false => name.to_string(),
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{LocalizedString, PluralValue, Section};
+
+#[cfg(test)]
+fn key(name: &str, comment: Option<&str>, value: StringValue) -> Key {
+    Key {
+        name: name.to_string(),
+        comment: comment.map(str::to_string),
+        tags: Vec::new(),
+        localizations: vec![LocalizedString {
+            language_code: "en".to_string(),
+            value,
+        }],
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn types_arguments_after_placeholders() {
+    let types = |text| arguments(&[text]).into_values().collect::<Vec<_>>();
+    assert_eq!(
+        types("%1$s has %2$d of %3$.2f"),
+        vec!["String", "Int", "Double"]
+    );
+    assert_eq!(types("%2$ld left"), vec!["Any?", "Long"]);
+    assert_eq!(types("100%% done"), Vec::<&str>::new());
+}
+
+#[test]
+fn names_objects_and_functions() {
+    assert_eq!(object_name("main_screen"), "MainScreen");
+    assert_eq!(object_name("strings"), "Strings");
+    assert_eq!(function_name("login_screen_title"), "loginScreenTitle");
+    assert_eq!(function_name("Settings.Title"), "settingsTitle");
+    assert_eq!(function_name("2fa_code"), "_2faCode");
+    assert_eq!(escape_name("object"), "`object`");
+}
+
+#[test]
+fn renders_accessors() {
+    let file = File {
+        sections: vec![Section {
+            name: None,
+            keys: vec![
+                key(
+                    "greeting",
+                    Some("Shown on start"),
+                    StringValue::Single("Hello, %1$s!".to_string()),
+                ),
+                key(
+                    "songs",
+                    None,
+                    StringValue::Plural {
+                        quantities: vec![
+                            PluralValue {
+                                quantity: "one".to_string(),
+                                text: "%d song".to_string(),
+                            },
+                            PluralValue {
+                                quantity: "other".to_string(),
+                                text: "%d songs".to_string(),
+                            },
+                        ],
+                    },
+                ),
+            ],
+        }],
+    };
+    let expected = "// Generated by utas, do not edit
+package com.example
+
+import android.content.Context
+import com.example.R
+
+object Strings {
+    /** Shown on start */
+    fun greeting(context: Context, arg1: String): String =
+        context.getString(R.string.greeting, arg1)
+
+    fun songs(context: Context, quantity: Int, arg1: Int): String =
+        context.resources.getQuantityString(R.plurals.songs, quantity, arg1)
+}
+";
+    assert_eq!(render(&file, "com.example", "Strings", &None), expected);
+}
@@ -0,0 +1,62 @@
+//! Generates Android and iOS string resources from twine files.
+//!
+//! Sources are loaded and parsed, validated against the generators to run and then
+//! generated, so `build.rs` scripts can do in-process what the binary does:
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//! use utas::{ErrorMode, GenOptions, InputFilter, Registry};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let filter = InputFilter::new(true, &["**/*.twine".to_string()], &[])?;
+//! let sources = utas::load_sources(&[PathBuf::from("strings")], &filter, ErrorMode::Collect)?;
+//! let registry = Registry::builtin();
+//! let platforms = ["android".to_string()];
+//! utas::validate(&registry, &platforms, &sources)?;
+//! let options = GenOptions {
+//!     output_dir: PathBuf::from("app/src/main/res"),
+//!     default_lang: Some("en".to_string()),
+//!     file_name: None,
+//! };
+//! utas::generate(&registry, &platforms, &sources, &options)?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod android_gen;
+mod android_import;
+mod arb_gen;
+mod cli;
+mod compose_gen;
+mod diff;
+mod escape;
+mod exchange;
+mod generator;
+mod input;
+mod ios_gen;
+mod ios_import;
+mod kotlin_gen;
+mod lock;
+mod output;
+mod parse;
+mod pipeline;
+mod plural;
+mod plugin_gen;
+mod po;
+mod pseudo;
+mod spreadsheet;
+mod stats;
+mod swift_gen;
+mod template_gen;
+mod ts_gen;
+mod twine;
+mod web_gen;
+mod xcstrings_gen;
+mod xliff;
+
+pub use cli::run as run_cli;
+pub use generator::{Generator, Registry};
+pub use input::InputFilter;
+pub use output::OutputFiles;
+pub use parse::{parse, File, Key, LocalizedString, PluralValue, Section, StringValue};
+pub use pipeline::{generate, load_sources, validate, ErrorMode, GenOptions, Source};
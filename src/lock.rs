@@ -0,0 +1,271 @@
+//! Lock file of the default language texts translations were made from.
+//!
+//! For every translation the lock keeps a hash of the default language text it was last
+//! confirmed against. A translation whose default language text changed since then is
+//! stale until it is marked reviewed. Translations the lock doesn't know yet are taken
+//! as up to date, so the first run records the current state.
+
+use anyhow::{anyhow, Ok, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::parse::{Key, StringValue};
+use crate::pipeline::Source;
+use crate::plural;
+
+/// Hashes by locale, by key name
+type Hashes = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub struct Lock {
+    /// Single strings and plurals are kept apart, like Android resources
+    #[serde(default)]
+    pub strings: Hashes,
+    #[serde(default)]
+    pub plurals: Hashes,
+}
+
+/// Translation whose default language text changed since it was confirmed
+#[derive(PartialEq, Eq, Debug)]
+pub struct Stale {
+    pub key: String,
+    pub plural: bool,
+    pub locale: String,
+}
+
+impl std::fmt::Display for Stale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.plural {
+            true => "plural",
+            false => "string",
+        };
+        write!(
+            f,
+            "{} of {} \"{}\" is stale, the default language text changed",
+            self.locale, kind, self.key
+        )
+    }
+}
+
+impl Lock {
+    /// Reads the lock file at `path`, an empty lock if there is none yet
+    pub fn read(path: &Path) -> Result<Lock> {
+        if !path.exists() {
+            return Ok(Lock::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+        serde_json::from_str(&text)
+            .map_err(|err| anyhow!("Invalid lock file {}: {}", path.display(), err))
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let mut result = serde_json::to_string_pretty(self)?;
+        result.push('\n');
+        Ok(result)
+    }
+
+    /// Records translations of `sources` the lock doesn't know yet and drops the ones which
+    /// no longer exist. Returns the stale translations, they keep their old hash.
+    pub fn update(&mut self, sources: &[Source], default_lang: &str) -> Vec<Stale> {
+        let mut stale = Vec::new();
+        let mut strings = Hashes::new();
+        let mut plurals = Hashes::new();
+        for (key, value) in translated_keys(sources, default_lang) {
+            let is_plural = matches!(value, StringValue::Plural { .. });
+            let (old, new) = match is_plural {
+                true => (&self.plurals, &mut plurals),
+                false => (&self.strings, &mut strings),
+            };
+            let hash = hash(value);
+            let old = old.get(&key.name);
+            let hashes = new.entry(key.name.clone()).or_default();
+            for locale in translations(key, default_lang) {
+                let confirmed = old
+                    .and_then(|hashes| hashes.get(locale))
+                    .unwrap_or(&hash)
+                    .clone();
+                if confirmed != hash {
+                    stale.push(Stale {
+                        key: key.name.clone(),
+                        plural: is_plural,
+                        locale: locale.to_string(),
+                    });
+                }
+                hashes.entry(locale.to_string()).or_insert(confirmed);
+            }
+        }
+        strings.retain(|_, hashes| !hashes.is_empty());
+        plurals.retain(|_, hashes| !hashes.is_empty());
+        self.strings = strings;
+        self.plurals = plurals;
+        stale
+    }
+
+    /// Confirms the translations of `keys` into `locales` against the current default
+    /// language texts, all keys or locales if none are given. Returns how many stale
+    /// translations became up to date.
+    pub fn review(
+        &mut self,
+        sources: &[Source],
+        default_lang: &str,
+        keys: &[String],
+        locales: &[String],
+    ) -> usize {
+        let mut reviewed = 0;
+        for (key, value) in translated_keys(sources, default_lang) {
+            if !keys.is_empty() && !keys.contains(&key.name) {
+                continue;
+            }
+            let hashes = match value {
+                StringValue::Single(_) => &mut self.strings,
+                StringValue::Plural { .. } => &mut self.plurals,
+            };
+            let hash = hash(value);
+            let hashes = hashes.entry(key.name.clone()).or_default();
+            for locale in translations(key, default_lang) {
+                if !locales.is_empty() && !locales.iter().any(|l| l == locale) {
+                    continue;
+                }
+                let old = hashes.insert(locale.to_string(), hash.clone());
+                if old.is_some_and(|old| old != hash) {
+                    reviewed += 1;
+                }
+            }
+        }
+        reviewed
+    }
+}
+
+/// Keys with their default language value, of keys with the same name and kind the first one
+fn translated_keys<'a>(
+    sources: &'a [Source],
+    default_lang: &'a str,
+) -> impl Iterator<Item = (&'a Key, &'a StringValue)> {
+    let mut seen = HashSet::new();
+    sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys)
+        .filter_map(move |key| {
+            let value = &key
+                .localizations
+                .iter()
+                .find(|str| str.language_code == default_lang)?
+                .value;
+            let is_plural = matches!(value, StringValue::Plural { .. });
+            seen.insert((&key.name, is_plural)).then_some((key, value))
+        })
+}
+
+/// Locales a key is translated into
+fn translations<'a>(key: &'a Key, default_lang: &'a str) -> impl Iterator<Item = &'a str> {
+    key.localizations
+        .iter()
+        .map(|str| str.language_code.as_str())
+        .filter(move |locale| *locale != default_lang)
+}
+
+/// FNV-1a hash of a default language value, plural quantities in CLDR order.
+/// It only needs to be stable, not secure, to tell whether a text changed.
+fn hash(value: &StringValue) -> String {
+    let text = match value {
+        StringValue::Single(text) => text.clone(),
+        StringValue::Plural { quantities } => {
+            let mut quantities: Vec<_> = quantities.iter().collect();
+            quantities.sort_by_key(|plural| plural::order(&plural.quantity));
+            quantities
+                .iter()
+                .map(|plural| format!("{}={}\n", plural.quantity, plural.text))
+                .collect()
+        }
+    };
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn source(text: &str) -> Source {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join("strings.txt");
+    fs::write(&path, text).unwrap();
+    Source {
+        name: "strings".to_string(),
+        file: crate::parse::parse(&path).unwrap(),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn flags_translations_of_changed_texts_until_reviewed() -> Result<()> {
+    let old = [source(
+        "[[Main]]
+  [title]
+    en = Hello
+    ru = Привет
+    de = Hallo
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:other = %d песен
+",
+    )];
+    let mut lock = Lock::default();
+    assert_eq!(lock.update(&old, "en"), vec![]);
+    assert_eq!(lock.strings["title"].len(), 2);
+
+    let new = [source(
+        "[[Main]]
+  [title]
+    en = Hello!
+    ru = Привет
+    de = Hallo
+  [songs]
+    en:other = %d songs
+    en:one = %d song
+    ru:other = %d песен
+",
+    )];
+    let stale = |key: &str, locale: &str| Stale {
+        key: key.to_string(),
+        plural: false,
+        locale: locale.to_string(),
+    };
+    // Reordered quantities are the same text
+    assert_eq!(
+        lock.update(&new, "en"),
+        vec![stale("title", "ru"), stale("title", "de")]
+    );
+    // Stale translations stay stale until reviewed
+    assert_eq!(lock.update(&new, "en").len(), 2);
+    assert_eq!(lock.review(&new, "en", &[], &["de".to_string()]), 1);
+    assert_eq!(lock.update(&new, "en"), vec![stale("title", "ru")]);
+    assert_eq!(lock.review(&new, "en", &["title".to_string()], &[]), 1);
+    assert_eq!(lock.update(&new, "en"), vec![]);
+    Ok(())
+}
+
+#[test]
+fn drops_removed_translations() -> Result<()> {
+    let mut lock = Lock::default();
+    lock.update(
+        &[source(
+            "[[Main]]\n  [title]\n    en = Hello\n    ru = Привет\n",
+        )],
+        "en",
+    );
+    lock.update(&[source("[[Main]]\n  [title]\n    en = Hello\n")], "en");
+    assert_eq!(lock, Lock::default());
+    assert_eq!(
+        Lock::default().render()?,
+        "{\n  \"strings\": {},\n  \"plurals\": {}\n}\n"
+    );
+    Ok(())
+}
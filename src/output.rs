@@ -0,0 +1,32 @@
+use anyhow::{Ok, Result};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Generated file contents by file path.
+///
+/// When generators add several files with the same path, the one added last wins,
+/// just like it would if the files were written one by one.
+pub type OutputFiles = BTreeMap<PathBuf, String>;
+
+/// Writes all files concurrently, creating missing directories
+pub fn write_files(files: &OutputFiles) -> Result<()> {
+    files.par_iter().try_for_each(|(path, contents)| {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    })
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn writes_files_creating_directories() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let path = dir.path().join("values-en").join("strings.xml");
+    write_files(&OutputFiles::from([(path.clone(), "content".to_string())]))?;
+    assert_eq!(fs::read_to_string(&path)?, "content");
+    Ok(())
+}
@@ -1,19 +1,19 @@
 use configparser::ini::{Ini, IniDefault};
 use const_format::concatcp;
-use indexmap::{map::Entry, IndexMap};
+use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use regex::{Captures, Match, Regex};
-use std::collections::HashSet;
-use std::fmt::Error;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs::File as FsFile;
 use std::io::{self, BufReader, Write};
 use std::io::{BufRead, BufWriter};
-use std::{borrow::Cow, fmt::format, path::Path};
+use std::{borrow::Cow, path::Path};
 use tempfile::NamedTempFile;
 
 // Taken from
 // https://developer.android.com/guide/topics/resources/string-resource.html#StylingWithHTML
-const ANDROID_SUPPORTED_TAGS: &'static [&'static str] = &[
+pub const ANDROID_SUPPORTED_TAGS: &[&str] = &[
     "annotation",
     "a",
     "i",
@@ -39,36 +39,44 @@ const ANDROID_SUPPORTED_TAGS: &'static [&'static str] = &[
     "p",
 ];
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct File {
     pub sections: Vec<Section>,
 }
 
-#[derive(Debug)]
+/// Keys under a `[[name]]` header
+#[derive(Debug, Serialize)]
 pub struct Section {
+    /// `None` for keys before the first header
+    pub name: Option<String>,
     pub keys: Vec<Key>,
 }
 
 /// Represents a string resource key with its localizations
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Key {
     pub name: String,
+    /// Note for translators, taken from the `comment` attribute
+    pub comment: Option<String>,
+    /// Values of the comma separated `tags` attribute
+    pub tags: Vec<String>,
     pub localizations: Vec<LocalizedString>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct LocalizedString {
     pub language_code: String,
     pub value: StringValue,
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone)]
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum StringValue {
     Single(String),
     Plural { quantities: Vec<PluralValue> },
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone)]
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Serialize)]
 pub struct PluralValue {
     /// quantity can be: "zero", "one", "two", "few", "many", and "other"
     pub quantity: String,
@@ -85,7 +93,7 @@ pub fn parse<T: AsRef<Path>>(path: T) -> Result<File, String> {
     // See NOTE_DEDUPLICATING_KEYS
     let temp_file =
         NamedTempFile::new().map_err(|_| "failed to create temporary file".to_string())?;
-    dedup_keys(&path, &temp_file).map_err(|error| (error.to_string() + " failed to dedup keys").to_string())?;
+    let key_sections = dedup_keys(&path, &temp_file).map_err(|error| (error.to_string() + " failed to dedup keys").to_string())?;
     let map = config.load(temp_file)?;
 
     // NOTE: twine has this structure
@@ -99,53 +107,75 @@ pub fn parse<T: AsRef<Path>>(path: T) -> Result<File, String> {
     //   key2 = value2
     // but configparser lib will ignore [[SectionX]] sections (see https://github.com/QEDK/configparser-rs/issues/37),
     // so here we will only see [subsection1, subsection2] returned by `config.sections()` and these will be
-    // string resource keys.
-    // We still will create a single "twine-section" struct in hopes of a future issue fix (seen above), then we'll
-    // be able to group "subsections" in "twine-section".
-    let mut section = Section {
-        keys: Vec::with_capacity(map.len()),
-    };
+    // string resource keys. `dedup_keys` remembers the twine-section of every key, keys are grouped
+    // into sections by it.
+    let mut sections: Vec<Section> = Vec::new();
     // Parses
     // [login_screen_title]
     // en = Login
     // ru = Логин
     for (resource_key_name, localizations) in map {
+        let section_name = key_sections.get(&resource_key_name);
+        if sections.last().is_none_or(|section| section.name.as_ref() != section_name) {
+            sections.push(Section {
+                name: section_name.cloned(),
+                keys: Vec::new(),
+            });
+        }
         let key = key_from_locale_value_map(resource_key_name, localizations)?;
-        section.keys.push(key);
+        sections.last_mut().unwrap().keys.push(key);
     }
-    Ok(File {
-        // For now only supporting a single section, see the comment above
-        sections: vec![section],
-    })
+    if sections.is_empty() {
+        sections.push(Section {
+            name: None,
+            keys: Vec::new(),
+        });
+    }
+    Ok(File { sections })
 }
 
 // TODO remove this function and write a custom parser
 // See NOTE_DEDUPLICATING_KEYS
-fn dedup_keys<T: AsRef<Path>, W: Write>(path: &T, temp_file: W) -> io::Result<()> {
+/// Returns the name of the `[[section]]` of every key, by the key name after deduplication
+fn dedup_keys<T: AsRef<Path>, W: Write>(
+    path: &T,
+    temp_file: W,
+) -> io::Result<HashMap<String, String>> {
     let f = FsFile::open(path)?;
     let f = BufReader::new(f);
     let mut of = BufWriter::new(temp_file);
     let mut keys: HashSet<String> = HashSet::new();
+    let mut key_sections: HashMap<String, String> = HashMap::new();
+    let mut section: Option<String> = None;
 
     for line in f.lines() {
         let l = line?;
         let maybe_key = l.trim();
-        let out_line: String;
-        if keys.iter().any(|x| x == maybe_key) {
-            out_line = format!(
-                "[{}{}]\n",
-                maybe_key.trim_matches(|c| c == '[' || c == ']'),
-                DEDUP_SUFFIX
-            );
+        let name = maybe_key.trim_matches(|c| c == '[' || c == ']');
+        if maybe_key.starts_with("[[") {
+            section = Some(name.to_string());
+        }
+        let out_line = if keys.iter().any(|x| x == maybe_key) {
+            let name = format!("{}{}", name, DEDUP_SUFFIX);
+            let out_line = format!("[{}]\n", name);
+            if let Some(section) = &section {
+                key_sections.entry(name).or_insert_with(|| section.clone());
+            }
+            out_line
         } else {
             if maybe_key.starts_with('[') && !maybe_key.starts_with("[[") {
                 keys.insert(maybe_key.to_string());
+                if let Some(section) = &section {
+                    key_sections
+                        .entry(name.to_string())
+                        .or_insert_with(|| section.clone());
+                }
             }
-            out_line = format!("{}\n", maybe_key);
-        }
-        of.write(out_line.as_bytes())?;
+            format!("{}\n", maybe_key)
+        };
+        of.write_all(out_line.as_bytes())?;
     }
-    Ok(())
+    Ok(key_sections)
 }
 
 const DEDUP_SUFFIX: &str = "_dedup";
@@ -155,7 +185,7 @@ const PLACEHOLDER_FLAGS_WIDTH_PRECISION_LENGTH: &str =
 const PLACEHOLDER_PARAMETER_FLAGS_WIDTH_PRECISION_LENGTH: &str =
     concatcp!(r"(\d+\$)?", PLACEHOLDER_FLAGS_WIDTH_PRECISION_LENGTH);
 const PLACEHOLDER_TYPES: &str = "[diufFeEgGxXoscpaA@]";
-const PLACEHOLDER_REGEX: &str = concatcp!(
+pub(crate) const PLACEHOLDER_REGEX: &str = concatcp!(
     "%",
     PLACEHOLDER_PARAMETER_FLAGS_WIDTH_PRECISION_LENGTH,
     PLACEHOLDER_TYPES
@@ -185,13 +215,43 @@ fn key_from_locale_value_map(
     }
 }
 
+/// Key attributes which are written like localizations but are not ones
+#[derive(Default)]
+struct KeyAttributes {
+    comment: Option<String>,
+    tags: Vec<String>,
+}
+
+impl KeyAttributes {
+    /// Returns true if `name` is an attribute and takes its value
+    fn take(&mut self, name: &str, value: &Option<String>) -> bool {
+        match name {
+            "comment" => {
+                self.comment = value.clone().filter(|comment| !comment.is_empty());
+                true
+            }
+            "tags" => {
+                self.tags = value
+                    .iter()
+                    .flat_map(|tags| tags.split(','))
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 fn key_from_locale_single_value_map(
     name: &str,
     raw_localizations: IndexMap<String, Option<String>>,
 ) -> Result<Key, String> {
     let mut localizations: Vec<LocalizedString> = Vec::with_capacity(raw_localizations.len());
+    let mut attributes = KeyAttributes::default();
     for (locale_name, string_value_opt) in raw_localizations {
-        if locale_name == "comment" || locale_name == "tags" {
+        if attributes.take(&locale_name, &string_value_opt) {
             continue;
         }
         let Some(string_value) = string_value_opt else {
@@ -206,6 +266,8 @@ fn key_from_locale_single_value_map(
     }
     let key = Key {
         name: name.to_string(),
+        comment: attributes.comment,
+        tags: attributes.tags,
         localizations,
     };
     Ok(key)
@@ -217,7 +279,11 @@ fn key_from_locale_plural_value_map(
 ) -> Result<Key, String> {
     let mut localizations: IndexMap<String, LocalizedString> =
         IndexMap::with_capacity(raw_localizations.len());
+    let mut attributes = KeyAttributes::default();
     for (locale_name_and_quantity, string_value_opt) in raw_localizations {
+        if attributes.take(&locale_name_and_quantity, &string_value_opt) {
+            continue;
+        }
         let Some(string_value) = string_value_opt else {
             println!("skipped key \"{}\" for \"{}\" because it's empty", locale_name_and_quantity, name);
             continue;
@@ -244,6 +310,8 @@ fn key_from_locale_plural_value_map(
     }
     let key = Key {
         name: name.to_string(),
+        comment: attributes.comment,
+        tags: attributes.tags,
         localizations: localizations.into_iter().map(|(_, value)| value).collect(),
     };
     Ok(key)
@@ -254,6 +322,10 @@ fn parse_localized_string_value(raw_value: String) -> Result<String, String> {
         static ref PLACEHOLDER_REGEX_RE: Regex = Regex::new(PLACEHOLDER_REGEX).unwrap();
     }
     let mut value = raw_value;
+    // Twine wraps values with leading or trailing whitespace in backticks
+    if let Some(unquoted) = value.strip_prefix('`').and_then(|v| v.strip_suffix('`')) {
+        value = unquoted.to_string();
+    }
     value = maybe_escape_characters(&value).to_string();
     value = maybe_replace_single_percent_with_double_percent(&value).to_string();
     if !PLACEHOLDER_REGEX_RE.is_match(&value) {
@@ -264,7 +336,7 @@ fn parse_localized_string_value(raw_value: String) -> Result<String, String> {
     Ok(value)
 }
 
-fn convert_twine_string_placeholder(raw_value: &str) -> Cow<str> {
+fn convert_twine_string_placeholder(raw_value: &str) -> Cow<'_, str> {
     lazy_static! {
         static ref TWINE_STRING_REPLACE_REGEX: Regex = Regex::new(
             format!(
@@ -276,26 +348,26 @@ fn convert_twine_string_placeholder(raw_value: &str) -> Cow<str> {
         .unwrap();
     }
     // TODO @dz @Parse avoid allocating new string if there's no match
-    TWINE_STRING_REPLACE_REGEX.replace_all(&raw_value, r"%${1}s")
+    TWINE_STRING_REPLACE_REGEX.replace_all(raw_value, r"%${1}s")
 }
 
-fn maybe_add_positional_numbers(input: &str) -> Cow<str> {
+fn maybe_add_positional_numbers(input: &str) -> Cow<'_, str> {
     lazy_static! {
         static ref NON_NUMBERED_PLACEHOLDER_REGEX_RE: Regex =
             Regex::new(NON_NUMBERED_PLACEHOLDER_REGEX).unwrap();
     }
-    let non_numbered_count = NON_NUMBERED_PLACEHOLDER_REGEX_RE.find_iter(&input).count();
+    let non_numbered_count = NON_NUMBERED_PLACEHOLDER_REGEX_RE.find_iter(input).count();
     if non_numbered_count <= 1 {
         return Cow::from(input);
     }
     let mut i = 0;
-    NON_NUMBERED_PLACEHOLDER_REGEX_RE.replace_all(&input, |caps: &Captures| {
+    NON_NUMBERED_PLACEHOLDER_REGEX_RE.replace_all(input, |caps: &Captures| {
         i += 1;
         format!("%{}${}", i, &caps[1])
     })
 }
 
-fn maybe_replace_single_percent_with_double_percent(input: &str) -> Cow<str> {
+fn maybe_replace_single_percent_with_double_percent(input: &str) -> Cow<'_, str> {
     lazy_static! {
         static ref SINGLE_PERCENT_REGEX_RE: Regex = Regex::new(SINGLE_PERCENT_REGEX).unwrap();
         static ref PLACEHOLDER_REGEX_RE: Regex = Regex::new(PLACEHOLDER_REGEX).unwrap();
@@ -326,7 +398,7 @@ fn percent_start(m: &Match) -> usize {
     m.start() + m.as_str().find('%').unwrap()
 }
 
-fn maybe_escape_characters(input: &str) -> Cow<str> {
+fn maybe_escape_characters(input: &str) -> Cow<'_, str> {
     let needs_escaping =
         input.contains("&") || input.contains("<") || input.contains("'") || input.contains("\"");
     if needs_escaping {
@@ -345,15 +417,16 @@ fn maybe_escape_characters(input: &str) -> Cow<str> {
 }
 
 fn escape_with_no_html_tags(input: &str) -> String {
-    return input
+    input
         .replace("&", "&amp;")
         .replace("<", "&lt;")
         .replace("'", "\\'")
-        .replace("\"", "\\\"");
+        .replace("\"", "\\\"")
 }
 
-fn escape_input_with_html_tags(input: &str) -> Cow<str> {
-    // contains [start,end) indexes of tag regions
+/// `[start, end)` byte ranges of supported HTML elements in `input`, from the opening
+/// tag to the end of the closing one, ordered by their start
+pub(crate) fn html_tag_regions(input: &str) -> Vec<(usize, usize)> {
     let mut tag_regions: Vec<(usize, usize)> = Vec::new();
     for tag in ANDROID_SUPPORTED_TAGS {
         let mut start = 0;
@@ -371,6 +444,13 @@ fn escape_input_with_html_tags(input: &str) -> Cow<str> {
             start = abs_end;
         }
     }
+    tag_regions.sort_by_key(|r| r.0);
+    tag_regions
+}
+
+fn escape_input_with_html_tags(input: &str) -> Cow<'_, str> {
+    // contains [start,end) indexes of tag regions
+    let tag_regions = html_tag_regions(input);
     if tag_regions.is_empty() {
         return Cow::Borrowed(input);
     }
@@ -381,7 +461,6 @@ fn escape_input_with_html_tags(input: &str) -> Cow<str> {
         result.push_str(&input[region.0..region.1]);
         result.push_str(&escape_with_no_html_tags(&input[region.1..]))
     } else {
-        tag_regions.sort_by_key(|r| r.0);
         // fully escape parts:
         // - before the first tag
         // - between tags
@@ -396,7 +475,99 @@ fn escape_input_with_html_tags(input: &str) -> Cow<str> {
             &input[tag_regions[tag_regions.len() - 1].1..],
         ));
     }
-    return Cow::Owned(result);
+    Cow::Owned(result)
+}
+
+/// Converts a text as written in a twine file into its parsed form, `unescape` reverts it
+pub fn parse_text(raw: &str) -> Result<String, String> {
+    parse_localized_string_value(raw.to_string())
+}
+
+/// Reverts the Android escaping done while parsing, giving the text as written in the source.
+///
+/// Placeholders are left in their parsed form, e.g. "%@" stays "%s".
+pub fn unescape(text: &str) -> String {
+    if !text.contains(['&', '\\']) {
+        return text.to_string();
+    }
+    text.replace("\\\"", "\"")
+        .replace("\\'", "'")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+/// Format placeholder of a parsed string, e.g. "%2$.2f"
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Placeholder {
+    /// 1-based index of the argument the placeholder takes
+    pub position: usize,
+    /// Conversion character, e.g. 's', 'd' or 'f'
+    pub conversion: char,
+    /// Placeholder as written in the text
+    pub spec: String,
+}
+
+impl Placeholder {
+    /// True if the argument index is written in the placeholder, e.g. "%1$s"
+    pub fn is_numbered(&self) -> bool {
+        self.spec.contains('$')
+    }
+
+    /// True if the placeholder takes an integer argument, e.g. "%d" or "%x"
+    pub fn is_integer(&self) -> bool {
+        "diuxXo".contains(self.conversion)
+    }
+
+    /// Flags, width, precision and length of the placeholder, e.g. ".2" for "%1$.2f"
+    pub fn modifiers(&self) -> &str {
+        let start = self.spec.find('$').map_or(1, |index| index + 1);
+        &self.spec[start..self.spec.len() - 1]
+    }
+}
+
+/// Placeholders of a parsed string in the order they appear, escaped "%%" is skipped
+pub fn placeholders(text: &str) -> Vec<Placeholder> {
+    let mut result = Vec::new();
+    replace_placeholders(text, "%%", |placeholder| {
+        result.push(placeholder.clone());
+        placeholder.spec.clone()
+    });
+    result
+}
+
+/// Replaces every placeholder of a parsed string with the result of `replacement`
+/// and every escaped "%%" with `percent`
+pub fn replace_placeholders(
+    text: &str,
+    percent: &str,
+    mut replacement: impl FnMut(&Placeholder) -> String,
+) -> String {
+    lazy_static! {
+        static ref PERCENT_OR_PLACEHOLDER_RE: Regex =
+            Regex::new(concatcp!("%%|", PLACEHOLDER_REGEX)).unwrap();
+        static ref POSITION_RE: Regex = Regex::new(r"^%(\d+)\$").unwrap();
+    }
+    let mut next_position = 0;
+    PERCENT_OR_PLACEHOLDER_RE
+        .replace_all(text, |caps: &Captures| {
+            let spec = &caps[0];
+            if spec == "%%" {
+                return percent.to_string();
+            }
+            let position = match POSITION_RE.captures(spec) {
+                Some(caps) => caps[1].parse().unwrap_or(0),
+                None => {
+                    next_position += 1;
+                    next_position
+                }
+            };
+            replacement(&Placeholder {
+                position,
+                conversion: spec.chars().last().unwrap(),
+                spec: spec.to_string(),
+            })
+        })
+        .to_string()
 }
 
 #[test]
@@ -654,6 +825,56 @@ fn parses_plural_keys_when_some_locales_miss_quantity() {
     }
 }
 
+#[test]
+fn parses_comment_and_tags_of_keys() {
+    let mut single = IndexMap::new();
+    single.insert("en".to_string(), Some("Find".to_string()));
+    single.insert("comment".to_string(), Some("Search button".to_string()));
+    single.insert("tags".to_string(), Some("android, ios".to_string()));
+    let key = key_from_locale_value_map("find".to_string(), single).unwrap();
+    assert_eq!(key.comment, Some("Search button".to_string()));
+    assert_eq!(key.tags, vec!["android", "ios"]);
+    assert_eq!(key.localizations.len(), 1);
+
+    let mut plural = IndexMap::new();
+    plural.insert("en:one".to_string(), Some("%d song".to_string()));
+    plural.insert("comment".to_string(), Some("Songs count".to_string()));
+    plural.insert("en:other".to_string(), Some("%d songs".to_string()));
+    let key = key_from_locale_value_map("songs".to_string(), plural).unwrap();
+    assert_eq!(key.comment, Some("Songs count".to_string()));
+    assert!(key.tags.is_empty());
+    assert_eq!(key.localizations.len(), 1);
+    assert_eq!(key.localizations[0].language_code, "en");
+}
+
+#[test]
+fn unescapes_parsed_text() {
+    let parsed = parse_localized_string_value("Tom's \"<cat>\" & %@".to_string()).unwrap();
+    assert_eq!(unescape(&parsed), "Tom's \"<cat>\" & %s");
+    assert_eq!(unescape("&amp;lt;"), "&lt;");
+}
+
+#[test]
+fn finds_placeholders() {
+    let found = placeholders("%%d of %2$.2f by %1$s, %d%%");
+    let specs: Vec<(usize, char, &str)> = found
+        .iter()
+        .map(|p| (p.position, p.conversion, p.spec.as_str()))
+        .collect();
+    assert_eq!(
+        specs,
+        vec![(2, 'f', "%2$.2f"), (1, 's', "%1$s"), (1, 'd', "%d")]
+    );
+}
+
+#[test]
+fn replaces_placeholders() {
+    let replaced = replace_placeholders("%1$.2f of %2$s, 5%%", "%", |p| {
+        format!("{{{}:{}{}}}", p.position, p.modifiers(), p.conversion)
+    });
+    assert_eq!(replaced, "{1:.2f} of {2:s}, 5%");
+}
+
 // NOTE_DEDUPLICATING_KEYS
 // Twine format allows duplicate keys, for example there could be a plurals
 // string and a regular string with the same key name.
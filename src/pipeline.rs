@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Ok, Result};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::generator::{Generator, Registry};
+use crate::input::{self, InputFilter};
+use crate::output;
+use crate::parse::{self as parser, File};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ErrorMode {
+    /// Stop at the first source that fails to parse
+    FailFast,
+    /// Parse every source and report all failures together
+    Collect,
+}
+
+/// Parsed twine file along with the name it was read from
+#[derive(Debug, Serialize)]
+pub struct Source {
+    pub name: String,
+    pub file: File,
+}
+
+/// Options shared by all generators
+#[derive(Clone, Debug, Serialize)]
+pub struct GenOptions {
+    pub output_dir: PathBuf,
+    pub default_lang: Option<String>,
+    /// Name of the iOS table, "Localizable" if not set
+    pub file_name: Option<String>,
+}
+
+/// Collects sources from `inputs`, parses them and runs every generator named in `generators`
+pub(crate) fn run_gen_pipeline(
+    registry: &Registry,
+    generators: &[String],
+    inputs: &[PathBuf],
+    filter: &InputFilter,
+    error_mode: ErrorMode,
+    options: &GenOptions,
+) -> Result<()> {
+    // Unknown names are reported before spending time on parsing
+    find_generators(registry, generators)?;
+    // Sources are parsed once and shared by every requested generator,
+    // so all of them are generated from the same validated input
+    let sources = load_sources(inputs, filter, error_mode)?;
+    generate(registry, generators, &sources, options)
+}
+
+pub fn load_sources(
+    inputs: &[PathBuf],
+    filter: &InputFilter,
+    error_mode: ErrorMode,
+) -> Result<Vec<Source>> {
+    parse_sources(&input::collect_sources(inputs, filter)?, error_mode)
+}
+
+pub fn generate(
+    registry: &Registry,
+    generators: &[String],
+    sources: &[Source],
+    options: &GenOptions,
+) -> Result<()> {
+    validate(registry, generators, sources)?;
+    for generator in find_generators(registry, generators)? {
+        output::write_files(&generator.render(sources, options)?)?;
+    }
+    Ok(())
+}
+
+/// Checks that `generators` can render `sources`: every generator is known and the ones
+/// writing a file per source don't get several sources with the same name
+pub fn validate(registry: &Registry, generators: &[String], sources: &[Source]) -> Result<()> {
+    if find_generators(registry, generators)?
+        .iter()
+        .any(|g| g.file_per_source())
+    {
+        check_unique_names(sources)?;
+    }
+    Ok(())
+}
+
+/// Files named after the sources would overwrite each other if two sources had the same name
+fn check_unique_names(sources: &[Source]) -> Result<()> {
+    let mut names: HashSet<&str> = HashSet::new();
+    for source in sources {
+        if !names.insert(&source.name) {
+            return Err(anyhow!(
+                "Several sources have the same name \"{}\", their output files would overwrite each other",
+                source.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Generators of `names`, unknown names are an error listing the available ones
+pub(crate) fn find_generators<'a>(registry: &'a Registry, names: &[String]) -> Result<Vec<&'a dyn Generator>> {
+    names
+        .iter()
+        .map(|name| {
+            registry.get(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown platform \"{}\", available: {}",
+                    name,
+                    registry.names().join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn parse_sources(paths: &[PathBuf], error_mode: ErrorMode) -> Result<Vec<Source>> {
+    // All sources are parsed concurrently, results are then handled in the input order
+    let parsed: Vec<_> = paths.par_iter().map(parser::parse).collect();
+
+    let mut sources = Vec::with_capacity(paths.len());
+    let mut errors: Vec<String> = Vec::new();
+    for (path, file) in paths.iter().zip(parsed) {
+        match to_source(path, file) {
+            Result::Ok(source) => sources.push(source),
+            Err(err) => match error_mode {
+                ErrorMode::FailFast => return Err(err),
+                ErrorMode::Collect => errors.push(err.to_string()),
+            },
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "{} of {} sources failed:\n{}",
+            errors.len(),
+            paths.len(),
+            errors.join("\n")
+        ));
+    }
+    Ok(sources)
+}
+
+fn to_source(path: &Path, file: Result<File, String>) -> Result<Source> {
+    let file = file.map_err(|err| anyhow!("Failed to parse {}: {}", path.display(), err))?;
+    let name = path
+        .file_stem()
+        .and_then(|os_str| os_str.to_str())
+        .ok_or(anyhow!("Cannot extract file name of {}", path.display()))?
+        .to_string();
+    Ok(Source { name, file })
+}
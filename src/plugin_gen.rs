@@ -0,0 +1,292 @@
+//! Generators implemented by external executables.
+//!
+//! The executable gets a JSON request on stdin:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "options": { "output_dir": "res", "default_lang": "en", "file_name": null },
+//!   "sources": [{
+//!     "name": "strings",
+//!     "file": { "sections": [{ "name": "Strings", "keys": [{
+//!       "name": "songs",
+//!       "comment": "Songs in a playlist",
+//!       "tags": ["ios"],
+//!       "localizations": [
+//!         { "language_code": "en", "value": { "single": "%d songs" } },
+//!         { "language_code": "ru", "value": { "plural": { "quantities": [
+//!           { "quantity": "one", "text": "%d песня" }
+//!         ] } } }
+//!       ]
+//!     }] }] }
+//!   }]
+//! }
+//! ```
+//!
+//! Sections keep the name of their `[[name]]` header, `null` for keys before the first one.
+//! Texts are passed as parsed, i.e. escaped for Android resources, see `parse::unescape`.
+//! The executable answers on stdout with the files to write, paths are relative to the
+//! output directory:
+//!
+//! ```json
+//! [{ "path": "en/strings.txt", "contents": "..." }]
+//! ```
+//!
+//! Anything written to stderr is passed through, a non-zero exit status fails the run.
+
+use anyhow::{anyhow, Ok, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::generator::Generator;
+use crate::output::OutputFiles;
+use crate::pipeline::{GenOptions, Source};
+
+/// Version of the request format, increased on incompatible changes
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Runs `program` with `args` as a generator named `name`
+pub struct PluginGenerator {
+    name: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl PluginGenerator {
+    pub fn new(name: String, program: String, args: Vec<String>) -> PluginGenerator {
+        PluginGenerator {
+            name,
+            program,
+            args,
+        }
+    }
+
+    /// Parses "NAME=COMMAND", the command is split on whitespace into the program and its arguments
+    pub fn from_spec(spec: &str) -> Result<PluginGenerator> {
+        let invalid = || anyhow!("Invalid plugin \"{}\", expected NAME=COMMAND", spec);
+        let (name, command) = spec.split_once('=').ok_or_else(invalid)?;
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or_else(invalid)?;
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        Ok(PluginGenerator::new(name, program, words.collect()))
+    }
+
+    fn run(&self, request: Vec<u8>) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| {
+                anyhow!(
+                    "Cannot run plugin \"{}\" ({}): {}",
+                    self.name,
+                    self.program,
+                    err
+                )
+            })?;
+
+        // Written from another thread, so a plugin which answers before reading
+        // all of its input doesn't block on a full stdout pipe
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = thread::spawn(move || stdin.write_all(&request));
+        let output = child.wait_with_output()?;
+        // A plugin may exit without reading the request, its status tells if that's fine
+        let _ = writer.join();
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Plugin \"{}\" failed with {}",
+                self.name,
+                output.status
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Generator for PluginGenerator {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let request = serde_json::to_vec(&Request {
+            version: PROTOCOL_VERSION,
+            options,
+            sources,
+        })?;
+        let response = self.run(request)?;
+        let files: Vec<ResponseFile> = serde_json::from_slice(&response)
+            .map_err(|err| anyhow!("Invalid response of plugin \"{}\": {}", self.name, err))?;
+        files
+            .into_iter()
+            .map(|file| {
+                check_relative(&file.path)
+                    .map_err(|err| anyhow!("Plugin \"{}\": {}", self.name, err))?;
+                Ok((options.output_dir.join(file.path), file.contents))
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    version: u32,
+    options: &'a GenOptions,
+    sources: &'a [Source],
+}
+
+#[derive(Deserialize)]
+struct ResponseFile {
+    path: PathBuf,
+    contents: String,
+}
+
+/// Plugins may only write inside the output directory
+fn check_relative(path: &Path) -> Result<()> {
+    let inside = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if inside && path.components().next().is_some() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "path {} is not inside the output directory",
+            path.display()
+        ))
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{File, Key, LocalizedString, PluralValue, Section, StringValue};
+
+#[cfg(test)]
+fn sources() -> Vec<Source> {
+    vec![Source {
+        name: "strings".to_string(),
+        file: File {
+            sections: vec![Section {
+                name: Some("Strings".to_string()),
+                keys: vec![Key {
+                    name: "songs".to_string(),
+                    comment: Some("Songs count".to_string()),
+                    tags: vec!["ios".to_string()],
+                    localizations: vec![
+                        LocalizedString {
+                            language_code: "en".to_string(),
+                            value: StringValue::Single("%d songs".to_string()),
+                        },
+                        LocalizedString {
+                            language_code: "ru".to_string(),
+                            value: StringValue::Plural {
+                                quantities: vec![PluralValue {
+                                    quantity: "one".to_string(),
+                                    text: "%d песня".to_string(),
+                                }],
+                            },
+                        },
+                    ],
+                }],
+            }],
+        },
+    }]
+}
+
+#[cfg(test)]
+fn options() -> GenOptions {
+    GenOptions {
+        output_dir: PathBuf::from("out"),
+        default_lang: Some("en".to_string()),
+        file_name: None,
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn serializes_request() -> Result<()> {
+    let sources = sources();
+    let options = options();
+    let request = serde_json::to_value(Request {
+        version: PROTOCOL_VERSION,
+        options: &options,
+        sources: &sources,
+    })?;
+    let expected = serde_json::json!({
+        "version": 1,
+        "options": { "output_dir": "out", "default_lang": "en", "file_name": null },
+        "sources": [{
+            "name": "strings",
+            "file": { "sections": [{ "name": "Strings", "keys": [{
+                "name": "songs",
+                "comment": "Songs count",
+                "tags": ["ios"],
+                "localizations": [
+                    { "language_code": "en", "value": { "single": "%d songs" } },
+                    { "language_code": "ru", "value": { "plural": { "quantities": [
+                        { "quantity": "one", "text": "%d песня" }
+                    ] } } }
+                ]
+            }] }] }
+        }]
+    });
+    assert_eq!(expected, request);
+    Ok(())
+}
+
+#[test]
+fn parses_plugin_spec() -> Result<()> {
+    let plugin = PluginGenerator::from_spec("Web=node gen.js --flat")?;
+    assert_eq!(plugin.name(), "web");
+    assert_eq!(plugin.program, "node");
+    assert_eq!(plugin.args, vec!["gen.js", "--flat"]);
+    assert!(PluginGenerator::from_spec("web").is_err());
+    assert!(PluginGenerator::from_spec("web= ").is_err());
+    Ok(())
+}
+
+#[test]
+fn accepts_only_paths_inside_output_dir() {
+    assert!(check_relative(Path::new("en/strings.txt")).is_ok());
+    assert!(check_relative(Path::new("./strings.txt")).is_ok());
+    assert!(check_relative(Path::new("../strings.txt")).is_err());
+    assert!(check_relative(Path::new("/tmp/strings.txt")).is_err());
+    assert!(check_relative(Path::new("")).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn renders_files_returned_by_plugin() -> Result<()> {
+    let script = r#"cat > /dev/null; echo '[{"path": "en/strings.txt", "contents": "songs"}]'"#;
+    let plugin = PluginGenerator::new(
+        "shell".to_string(),
+        "sh".to_string(),
+        vec!["-c".to_string(), script.to_string()],
+    );
+    let files = plugin.render(&sources(), &options())?;
+    assert_eq!(
+        files,
+        OutputFiles::from([(PathBuf::from("out/en/strings.txt"), "songs".to_string())])
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fails_when_plugin_fails() {
+    let plugin = PluginGenerator::new(
+        "shell".to_string(),
+        "sh".to_string(),
+        vec!["-c".to_string(), "exit 3".to_string()],
+    );
+    let err = plugin.render(&sources(), &options()).unwrap_err();
+    assert!(err.to_string().contains("\"shell\" failed"));
+}
@@ -0,0 +1,62 @@
+//! CLDR plural categories of locales.
+
+/// All plural categories in CLDR order
+pub const CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// Cardinal plural categories used by the language of `locale`, e.g. "ru-RU" uses
+/// one, few, many and other. `None` if the language isn't known.
+pub fn categories(locale: &str) -> Option<&'static [&'static str]> {
+    let categories: &[&str] = match language(locale).as_str() {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "my" | "lo" | "km" => &["other"],
+        "en" | "de" | "nl" | "sv" | "da" | "nb" | "no" | "nn" | "fi" | "et" | "el" | "hu"
+        | "tr" | "bg" | "az" | "kk" | "ky" | "uz" | "mn" | "hi" | "bn" | "ur" | "fa" | "sw"
+        | "gl" | "eu" | "af" | "sq" | "ka" | "hy" => &["one", "other"],
+        "fr" | "it" | "es" | "pt" | "ca" => &["one", "many", "other"],
+        "ru" | "uk" | "be" | "pl" | "cs" | "sk" | "lt" => &["one", "few", "many", "other"],
+        "ro" | "hr" | "sr" | "bs" => &["one", "few", "other"],
+        "lv" => &["zero", "one", "other"],
+        "sl" => &["one", "two", "few", "other"],
+        "he" => &["one", "two", "other"],
+        "ga" => &["one", "two", "few", "many", "other"],
+        "ar" | "cy" => &["zero", "one", "two", "few", "many", "other"],
+        _ => return None,
+    };
+    Some(categories)
+}
+
+/// Lowercase language of `locale`, e.g. "pt" of "pt-BR"
+pub fn language(locale: &str) -> String {
+    locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Index of `quantity` in CLDR order, unknown quantities go last
+pub fn order(quantity: &str) -> usize {
+    CATEGORIES
+        .iter()
+        .position(|category| *category == quantity)
+        .unwrap_or(CATEGORIES.len())
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn finds_categories_by_language() {
+    assert_eq!(
+        categories("ru-RU"),
+        Some(&["one", "few", "many", "other"][..])
+    );
+    assert_eq!(categories("zh_Hans"), Some(&["other"][..]));
+    assert_eq!(categories("pt-BR"), Some(&["one", "many", "other"][..]));
+    assert_eq!(categories("ca"), Some(&["one", "many", "other"][..]));
+    assert_eq!(categories("tlh"), None);
+}
+
+#[test]
+fn orders_quantities() {
+    let mut quantities = vec!["other", "few", "one", "custom"];
+    quantities.sort_by_key(|quantity| order(quantity));
+    assert_eq!(quantities, vec!["one", "few", "other", "custom"]);
+}
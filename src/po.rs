@@ -0,0 +1,516 @@
+//! Gettext PO and POT files for translators, e.g. for Poedit.
+//!
+//! Keys are entries with the key name as `msgctxt`. Plurals use `msgid_plural`, with
+//! `msgstr[n]` holding the n-th CLDR plural category of the locale.
+
+use anyhow::{anyhow, Ok, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::exchange::{export_entries, key_text, target_locales, SourceText, Translation};
+use crate::output::OutputFiles;
+use crate::parse::{Key, StringValue};
+use crate::pipeline::Source;
+use crate::plural;
+
+/// Name of the exported template
+pub const TEMPLATE_NAME: &str = "messages.pot";
+
+/// Header with the language the entries were translated from, checked on import
+const SOURCE_LANGUAGE_HEADER: &str = "X-Source-Language";
+
+/// Renders `messages.pot` and `<locale>.po` for every target locale, see `target_locales`
+pub fn export(
+    sources: &[Source],
+    default_lang: &str,
+    locales: &[String],
+    dir: &Path,
+) -> Result<OutputFiles> {
+    let mut files = OutputFiles::new();
+    files.insert(
+        dir.join(TEMPLATE_NAME),
+        render(sources, default_lang, None)?,
+    );
+    for locale in target_locales(sources, default_lang, locales)? {
+        let contents = render(sources, default_lang, Some(&locale))?;
+        files.insert(dir.join(format!("{}.po", locale)), contents);
+    }
+    Ok(files)
+}
+
+/// PO file of `locale`, or the template without translations if it's `None`
+fn render(sources: &[Source], default_lang: &str, locale: Option<&str>) -> Result<String> {
+    let mut result = header(default_lang, locale);
+    for source in sources {
+        for entry in export_entries(&source.file, default_lang, locale.unwrap_or(default_lang)) {
+            result.push('\n');
+            if let Some(comment) = &entry.key.comment {
+                for line in comment.lines() {
+                    result.push_str(&format!("#. {}\n", line));
+                }
+            }
+            result.push_str(&field("msgctxt", &entry.key.name));
+            let plural = entry.texts.iter().any(|text| text.quantity.is_some());
+            if !plural {
+                let text = &entry.texts[0];
+                result.push_str(&field("msgid", &text.source));
+                let target = text.target.as_deref().filter(|_| locale.is_some());
+                result.push_str(&field("msgstr", target.unwrap_or_default()));
+                continue;
+            }
+            let (singular, plural) = plural_sources(entry.key, default_lang);
+            result.push_str(&field("msgid", &singular));
+            result.push_str(&field("msgid_plural", &plural));
+            match locale {
+                Some(locale) => {
+                    // Texts are in the CLDR order of the locale, which the plural formula follows
+                    plural_forms(locale).ok_or_else(|| unknown_plurals(locale))?;
+                    for (index, text) in entry.texts.iter().enumerate() {
+                        let target = text.target.as_deref().unwrap_or_default();
+                        result.push_str(&field(&format!("msgstr[{}]", index), target));
+                    }
+                }
+                None => {
+                    result.push_str(&field("msgstr[0]", ""));
+                    result.push_str(&field("msgstr[1]", ""));
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn header(default_lang: &str, locale: Option<&str>) -> String {
+    let plural_forms = match locale {
+        Some(locale) => plural_forms(locale),
+        None => Some("nplurals=INTEGER; plural=EXPRESSION;".to_string()),
+    };
+    let mut fields = vec![
+        format!("Language: {}", locale.unwrap_or_default()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=UTF-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+    ];
+    // Locales with unknown plural rules can still have their single strings translated
+    if let Some(plural_forms) = plural_forms {
+        fields.push(format!("Plural-Forms: {}", plural_forms));
+    }
+    fields.push(format!("{}: {}", SOURCE_LANGUAGE_HEADER, default_lang));
+    let mut result = "msgid \"\"\nmsgstr \"\"\n".to_string();
+    for line in fields {
+        result.push_str(&format!("\"{}\\n\"\n", quote(&line)));
+    }
+    result
+}
+
+/// Texts of the default language for `msgid` and `msgid_plural`: "one" and "other"
+/// if there are such quantities, the first and the last ones otherwise
+fn plural_sources(key: &Key, default_lang: &str) -> (String, String) {
+    let quantities: Vec<&str> = key
+        .localizations
+        .iter()
+        .find(|str| str.language_code == default_lang)
+        .map(|str| match &str.value {
+            StringValue::Plural { quantities } => {
+                quantities.iter().map(|q| q.quantity.as_str()).collect()
+            }
+            StringValue::Single(_) => Vec::new(),
+        })
+        .unwrap_or_default();
+    let text = |preferred: &str, fallback: Option<&&str>| {
+        key_text(key, default_lang, &Some(preferred.to_string()))
+            .or_else(|| key_text(key, default_lang, &fallback.map(|q| q.to_string())))
+            .unwrap_or_default()
+    };
+    (
+        text("one", quantities.first()),
+        text("other", quantities.last()),
+    )
+}
+
+/// `msgid "text"`, multiline texts are split after every newline like gettext does
+fn field(name: &str, text: &str) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    if lines.len() < 2 {
+        return format!("{} \"{}\"\n", name, quote(text));
+    }
+    let mut result = format!("{} \"\"\n", name);
+    for line in lines {
+        result.push_str(&format!("\"{}\"\n", quote(line)));
+    }
+    result
+}
+
+fn quote(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn unquote(text: &str) -> Result<String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("expected a quoted string, got {}", text))?;
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => return Err(anyhow!("unfinished escape in {}", text)),
+        }
+    }
+    Ok(result)
+}
+
+/// `Plural-Forms` value of `locale` with the indexes of its CLDR categories
+fn plural_forms(locale: &str) -> Option<String> {
+    let count = plural::categories(locale)?.len();
+    let expression = match plural::language(locale).as_str() {
+        "hi" | "bn" | "fa" => "(n > 1)",
+        "fr" | "pt" => "(n == 0 || n == 1) ? 0 : (n != 0 && n % 1000000 == 0) ? 1 : 2",
+        "it" | "es" | "ca" => "n == 1 ? 0 : (n != 0 && n % 1000000 == 0) ? 1 : 2",
+        "ru" | "uk" | "be" | "hr" | "sr" | "bs" => {
+            "(n % 10 == 1 && n % 100 != 11) ? 0 : (n % 10 >= 2 && n % 10 <= 4 && (n % 100 < 12 || n % 100 > 14)) ? 1 : 2"
+        }
+        "pl" => "n == 1 ? 0 : (n % 10 >= 2 && n % 10 <= 4 && (n % 100 < 12 || n % 100 > 14)) ? 1 : 2",
+        "cs" | "sk" => "n == 1 ? 0 : (n >= 2 && n <= 4) ? 1 : 3",
+        "lt" => {
+            "(n % 10 == 1 && (n % 100 < 11 || n % 100 > 19)) ? 0 : (n % 10 >= 2 && (n % 100 < 11 || n % 100 > 19)) ? 1 : 3"
+        }
+        "ro" => "n == 1 ? 0 : (n == 0 || (n % 100 >= 1 && n % 100 <= 19)) ? 1 : 2",
+        "lv" => "(n % 10 == 0 || (n % 100 >= 11 && n % 100 <= 19)) ? 0 : (n % 10 == 1 && n % 100 != 11) ? 1 : 2",
+        "sl" => "n % 100 == 1 ? 0 : n % 100 == 2 ? 1 : (n % 100 == 3 || n % 100 == 4) ? 2 : 3",
+        "he" => "n == 1 ? 0 : n == 2 ? 1 : 2",
+        "ga" => "n == 1 ? 0 : n == 2 ? 1 : (n >= 3 && n <= 6) ? 2 : (n >= 7 && n <= 10) ? 3 : 4",
+        "ar" => {
+            "n == 0 ? 0 : n == 1 ? 1 : n == 2 ? 2 : (n % 100 >= 3 && n % 100 <= 10) ? 3 : n % 100 >= 11 ? 4 : 5"
+        }
+        "cy" => "n == 0 ? 0 : n == 1 ? 1 : n == 2 ? 2 : n == 3 ? 3 : n == 6 ? 4 : 5",
+        _ if count == 1 => "0",
+        _ => "(n != 1)",
+    };
+    Some(format!("nplurals={}; plural={};", count, expression))
+}
+
+fn unknown_plurals(locale: &str) -> anyhow::Error {
+    anyhow!("Unknown plural categories of locale \"{}\"", locale)
+}
+
+/// Entry of a PO file, `strings` are indexed by `msgstr[n]`, a plain `msgstr` is 0
+#[derive(Default)]
+struct Entry {
+    fuzzy: bool,
+    context: Option<String>,
+    id: Option<String>,
+    id_plural: Option<String>,
+    strings: BTreeMap<usize, String>,
+}
+
+/// Field continued by the following quoted lines
+enum Field {
+    Context,
+    Id,
+    IdPlural,
+    String(usize),
+}
+
+/// Reads translated entries of a PO file, untranslated and fuzzy entries are skipped
+pub fn read(path: &Path) -> Result<Vec<Translation>> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+    let file_name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+    parse(&text, &file_name).map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))
+}
+
+fn parse(text: &str, file_name: &str) -> Result<Vec<Translation>> {
+    let entries = parse_entries(text)?;
+    let header = entries
+        .iter()
+        .find(|entry| entry.context.is_none() && entry.id.as_deref() == Some(""))
+        .and_then(|entry| entry.strings.get(&0))
+        .map(|header| parse_header(header))
+        .unwrap_or_default();
+    let locale = header
+        .get("Language")
+        .filter(|locale| !locale.is_empty())
+        .ok_or_else(|| anyhow!("no Language header"))?;
+    let source_lang = header.get(SOURCE_LANGUAGE_HEADER);
+
+    let mut result = Vec::new();
+    for entry in entries {
+        let (Some(key), Some(id), false) = (&entry.context, &entry.id, entry.fuzzy) else {
+            continue;
+        };
+        let source = |text: &str| {
+            source_lang.map(|locale| SourceText {
+                locale: locale.clone(),
+                text: text.to_string(),
+            })
+        };
+        let Some(id_plural) = &entry.id_plural else {
+            if let Some(text) = entry.strings.get(&0).filter(|text| !text.is_empty()) {
+                result.push(Translation {
+                    key: key.clone(),
+                    quantity: None,
+                    locale: locale.clone(),
+                    source: source(id),
+                    text: text.clone(),
+                    origin: format!("{}: {}", file_name, key),
+                });
+            }
+            continue;
+        };
+        let categories = plural::categories(locale).ok_or_else(|| unknown_plurals(locale))?;
+        for (index, text) in entry.strings.iter().filter(|(_, text)| !text.is_empty()) {
+            let quantity = categories
+                .get(*index)
+                .ok_or_else(|| anyhow!("\"{}\" has no plural form {}", key, index))?;
+            result.push(Translation {
+                key: key.clone(),
+                quantity: Some(quantity.to_string()),
+                locale: locale.clone(),
+                source: source(if *quantity == "one" { id } else { id_plural }),
+                text: text.clone(),
+                origin: format!("{}: {}:{}", file_name, key, quantity),
+            });
+        }
+    }
+    Ok(result)
+}
+
+fn parse_entries(text: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut entry = Entry::default();
+    let mut field: Option<Field> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |err: anyhow::Error| anyhow!("line {}: {}", index + 1, err);
+        // A comment or a new context after the strings of an entry start the next entry
+        let starts_entry = line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("msgctxt ")
+            || line.starts_with("msgid ");
+        if starts_entry && !entry.strings.is_empty() {
+            entries.push(std::mem::take(&mut entry));
+            field = None;
+        }
+        if line.is_empty() || line.starts_with("#~") {
+            continue;
+        }
+        if let Some(flags) = line.strip_prefix("#,") {
+            entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('"') {
+            let text = unquote(line).map_err(error)?;
+            let target = match field {
+                Some(Field::Context) => entry.context.as_mut(),
+                Some(Field::Id) => entry.id.as_mut(),
+                Some(Field::IdPlural) => entry.id_plural.as_mut(),
+                Some(Field::String(index)) => entry.strings.get_mut(&index),
+                None => None,
+            };
+            target
+                .ok_or_else(|| error(anyhow!("string without a keyword")))?
+                .push_str(&text);
+            continue;
+        }
+        let (keyword, value) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| error(anyhow!("unexpected \"{}\"", line)))?;
+        let value = unquote(value.trim()).map_err(error)?;
+        field = Some(match keyword {
+            "msgctxt" => {
+                entry.context = Some(value);
+                Field::Context
+            }
+            "msgid" => {
+                entry.id = Some(value);
+                Field::Id
+            }
+            "msgid_plural" => {
+                entry.id_plural = Some(value);
+                Field::IdPlural
+            }
+            "msgstr" => {
+                entry.strings.insert(0, value);
+                Field::String(0)
+            }
+            _ => {
+                let index = keyword
+                    .strip_prefix("msgstr[")
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .ok_or_else(|| error(anyhow!("unknown keyword \"{}\"", keyword)))?;
+                entry.strings.insert(index, value);
+                Field::String(index)
+            }
+        });
+    }
+    if entry.id.is_some() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// `Name: value` lines of the header entry
+fn parse_header(header: &str) -> BTreeMap<String, String> {
+    header
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn sources() -> Vec<Source> {
+    let mut source = crate::exchange::source(
+        "[[Main]]
+  [find]
+    en = Find \"%s\"\\nnow
+    ru = Найти
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+    ru:many = %d песен
+",
+    );
+    // Comments imported from other formats may span several lines
+    source.file.sections[0].keys[0].comment = Some("Toolbar button\nKeep it short".to_string());
+    vec![source]
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn exports_po_files_and_template() -> Result<()> {
+    let files = export(&sources(), "en", &[], Path::new("out"))?;
+    let expected = r#"msgid ""
+msgstr ""
+"Language: ru\n"
+"MIME-Version: 1.0\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+"Content-Transfer-Encoding: 8bit\n"
+"Plural-Forms: nplurals=4; plural=(n % 10 == 1 && n % 100 != 11) ? 0 : (n % 10 >= 2 && n % 10 <= 4 && (n % 100 < 12 || n % 100 > 14)) ? 1 : 2;\n"
+"X-Source-Language: en\n"
+
+#. Toolbar button
+#. Keep it short
+msgctxt "find"
+msgid "Find \"%s\"\\nnow"
+msgstr "Найти"
+
+msgctxt "songs"
+msgid "%d song"
+msgid_plural "%d songs"
+msgstr[0] "%d песня"
+msgstr[1] ""
+msgstr[2] "%d песен"
+msgstr[3] ""
+"#;
+    assert_eq!(files[Path::new("out/ru.po")], expected);
+    let template = &files[Path::new("out/messages.pot")];
+    assert!(template.contains("\"Language: \\n\""));
+    assert!(template.contains("msgid_plural \"%d songs\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n"));
+    assert!(template.contains("\\\\nnow\"\nmsgstr \"\"\n"));
+    Ok(())
+}
+
+#[test]
+fn reads_translations_skipping_fuzzy_ones() -> Result<()> {
+    let text = r#"msgid ""
+msgstr ""
+"Language: ru\n"
+"X-Source-Language: en\n"
+
+msgctxt "find"
+msgid "Find"
+msgstr ""
+"Найти\n"
+"всё"
+
+#, fuzzy
+msgctxt "close"
+msgid "Close"
+msgstr "Закрыть"
+
+msgctxt "songs"
+msgid "%d song"
+msgid_plural "%d songs"
+msgstr[0] "%d песня"
+msgstr[1] ""
+msgstr[2] "%d песен"
+
+#~ msgctxt "old"
+#~ msgid "Old"
+#~ msgstr "Старый"
+"#;
+    let translations = parse(text, "ru.po")?;
+    let summary: Vec<(&str, Option<&str>, &str, &str)> = translations
+        .iter()
+        .map(|t| {
+            (
+                t.key.as_str(),
+                t.quantity.as_deref(),
+                t.source.as_ref().unwrap().text.as_str(),
+                t.text.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("find", None, "Find", "Найти\nвсё"),
+            ("songs", Some("one"), "%d song", "%d песня"),
+            ("songs", Some("many"), "%d songs", "%d песен"),
+        ]
+    );
+    assert_eq!(translations[2].origin, "ru.po: songs:many");
+    Ok(())
+}
+
+#[test]
+fn fails_without_language() {
+    assert!(parse(
+        "msgid \"\"\nmsgstr \"\"\n\"Language: \\n\"\n",
+        "messages.pot"
+    )
+    .is_err());
+}
+
+#[test]
+fn matches_plural_forms_to_categories() {
+    assert_eq!(
+        plural_forms("pt-BR").unwrap(),
+        "nplurals=3; plural=(n == 0 || n == 1) ? 0 : (n != 0 && n % 1000000 == 0) ? 1 : 2;"
+    );
+    assert_eq!(
+        plural_forms("ca").unwrap(),
+        "nplurals=3; plural=n == 1 ? 0 : (n != 0 && n % 1000000 == 0) ? 1 : 2;"
+    );
+    assert_eq!(plural_forms("en").unwrap(), "nplurals=2; plural=(n != 1);");
+}
@@ -0,0 +1,301 @@
+//! Pseudo-localization: locales made up from the default language before translations arrive,
+//! so truncated, concatenated and hard-coded texts stand out. Placeholders, HTML tags and
+//! escapes are kept as they are.
+
+use anyhow::{anyhow, Ok, Result};
+use clap::ValueEnum;
+use const_format::concatcp;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::parse::{self, LocalizedString, PluralValue, StringValue};
+use crate::pipeline::Source;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum PseudoLocale {
+    /// Accented letters, longer texts in brackets
+    #[value(name = "en-XA")]
+    Accented,
+    /// Words written right-to-left with bidi controls
+    #[value(name = "ar-XB")]
+    Bidi,
+}
+
+impl PseudoLocale {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PseudoLocale::Accented => "en-XA",
+            PseudoLocale::Bidi => "ar-XB",
+        }
+    }
+}
+
+/// How "en-XA" texts are expanded
+#[derive(Clone, Copy, Debug)]
+pub struct PseudoOptions {
+    /// Length added to a text, in percent of its length
+    pub padding: usize,
+    /// Wrap texts in "[" and "]", so cut off ends are visible
+    pub brackets: bool,
+}
+
+impl Default for PseudoOptions {
+    fn default() -> Self {
+        PseudoOptions {
+            padding: 30,
+            brackets: true,
+        }
+    }
+}
+
+/// Words the padding is made of, like Android's pseudolocalizer does
+const PADDING_WORDS: &[&str] = &[
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+    "twenty",
+];
+
+/// Right-to-left mark and override around a word, the pop directional formatting ends it
+const RLM: char = '\u{200F}';
+const RLO: char = '\u{202E}';
+const PDF: char = '\u{202C}';
+
+/// Adds `locales` to every key of `sources` which has a text in `default_lang`.
+/// Keys which already have a pseudo locale keep it.
+pub fn add_locales(
+    sources: &mut [Source],
+    default_lang: &str,
+    locales: &[PseudoLocale],
+    options: &PseudoOptions,
+) -> Result<()> {
+    let keys = sources
+        .iter_mut()
+        .flat_map(|source| &mut source.file.sections)
+        .flat_map(|section| &mut section.keys);
+    let mut found = false;
+    for key in keys {
+        let Some(value) = key
+            .localizations
+            .iter()
+            .find(|str| str.language_code == default_lang)
+            .map(|str| str.value.clone())
+        else {
+            continue;
+        };
+        found = true;
+        for locale in locales {
+            if key
+                .localizations
+                .iter()
+                .any(|str| str.language_code == locale.code())
+            {
+                continue;
+            }
+            key.localizations.push(LocalizedString {
+                language_code: locale.code().to_string(),
+                value: pseudo_value(&value, *locale, options),
+            });
+        }
+    }
+    match found {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "No strings in the default language \"{}\" to make pseudo locales from",
+            default_lang
+        )),
+    }
+}
+
+fn pseudo_value(value: &StringValue, locale: PseudoLocale, options: &PseudoOptions) -> StringValue {
+    match value {
+        StringValue::Single(text) => StringValue::Single(localize(text, locale, options)),
+        StringValue::Plural { quantities } => StringValue::Plural {
+            quantities: quantities
+                .iter()
+                .map(|plural| PluralValue {
+                    quantity: plural.quantity.clone(),
+                    text: localize(&plural.text, locale, options),
+                })
+                .collect(),
+        },
+    }
+}
+
+/// Pseudo-localizes a parsed text
+pub fn localize(text: &str, locale: PseudoLocale, options: &PseudoOptions) -> String {
+    let segments = segments(text);
+    match locale {
+        PseudoLocale::Accented => {
+            let mut result = String::new();
+            let mut length = 0;
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => {
+                        length += text.chars().count();
+                        result.extend(text.chars().map(accented));
+                    }
+                    Segment::Kept(kept) => result.push_str(kept),
+                }
+            }
+            result.push_str(&padding(length * options.padding / 100));
+            match options.brackets {
+                true => format!("[{}]", result),
+                false => result,
+            }
+        }
+        PseudoLocale::Bidi => {
+            let mut result = String::new();
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => result.push_str(&mirrored(text)),
+                    Segment::Kept(kept) => result.push_str(kept),
+                }
+            }
+            result
+        }
+    }
+}
+
+enum Segment<'a> {
+    /// Text to pseudo-localize
+    Text(&'a str),
+    /// Placeholder, escape or HTML tag
+    Kept(&'a str),
+}
+
+/// Splits a parsed text into translatable parts and the ones to keep. Markup of the tag
+/// regions found like `parse` escapes them is kept, the text inside the tags is not.
+fn segments(text: &str) -> Vec<Segment<'_>> {
+    lazy_static! {
+        static ref KEPT_RE: Regex = Regex::new(concatcp!(
+            r"%%|",
+            parse::PLACEHOLDER_REGEX,
+            r"|\\.|&[A-Za-z0-9#]+;"
+        ))
+        .unwrap();
+        static ref MARKUP_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+    let mut kept: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in parse::html_tag_regions(text) {
+        kept.extend(
+            MARKUP_RE
+                .find_iter(&text[start..end])
+                .map(|found| (start + found.start(), start + found.end())),
+        );
+    }
+    for found in KEPT_RE.find_iter(text) {
+        let inside_markup = kept
+            .iter()
+            .any(|(start, end)| found.start() < *end && *start < found.end());
+        if !inside_markup {
+            kept.push((found.start(), found.end()));
+        }
+    }
+    kept.sort();
+
+    let mut result = Vec::new();
+    let mut position = 0;
+    for (start, end) in kept {
+        if start > position {
+            result.push(Segment::Text(&text[position..start]));
+        }
+        result.push(Segment::Kept(&text[start..end]));
+        position = end;
+    }
+    if position < text.len() {
+        result.push(Segment::Text(&text[position..]));
+    }
+    result
+}
+
+fn accented(c: char) -> char {
+    const LOWER: &str = "åƀçðéƒĝĥîĵķļɱñöþǫŕšţûṽŵẋýž";
+    const UPPER: &str = "ÅƁÇÐÉƑĜĤÎĴĶĻṀÑÖÞǪŔŠŢÛṼŴẊÝŽ";
+    match c {
+        'a'..='z' => LOWER.chars().nth(c as usize - 'a' as usize).unwrap(),
+        'A'..='Z' => UPPER.chars().nth(c as usize - 'A' as usize).unwrap(),
+        _ => c,
+    }
+}
+
+/// Words of at least `length` characters with a leading space, empty for no length
+fn padding(length: usize) -> String {
+    let mut result = String::new();
+    for word in PADDING_WORDS.iter().cycle() {
+        if result.chars().count() >= length {
+            break;
+        }
+        result.push(' ');
+        result.push_str(word);
+    }
+    result
+}
+
+/// Every word is forced right-to-left, whitespace between the words is kept
+fn mirrored(text: &str) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() {
+            push_word(&mut result, &mut word);
+            result.push(c);
+        } else {
+            word.push(c);
+        }
+    }
+    push_word(&mut result, &mut word);
+    result
+}
+
+fn push_word(result: &mut String, word: &mut String) {
+    if word.is_empty() {
+        return;
+    }
+    result.extend([RLM, RLO]);
+    result.push_str(word);
+    result.extend([PDF, RLM]);
+    word.clear();
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn accents_and_pads_texts() {
+    let options = PseudoOptions::default();
+    let localized = |text| localize(text, PseudoLocale::Accented, &options);
+    assert_eq!(localized("Hello"), "[Ĥéļļö one]");
+    assert_eq!(
+        localized("Hi %1$s, <b>save</b> 50%% \\'now\\' &amp; go"),
+        "[Ĥî %1$s, <b>šåṽé</b> 50%% \\'ñöŵ\\' &amp; ĝö one two]"
+    );
+    let options = PseudoOptions {
+        padding: 0,
+        brackets: false,
+    };
+    assert_eq!(localize("Go", PseudoLocale::Accented, &options), "Ĝö");
+}
+
+#[test]
+fn mirrors_words_with_bidi_controls() {
+    let options = PseudoOptions::default();
+    assert_eq!(
+        localize("Hi %d <i>you</i>", PseudoLocale::Bidi, &options),
+        "\u{200F}\u{202E}Hi\u{202C}\u{200F} %d <i>\u{200F}\u{202E}you\u{202C}\u{200F}</i>"
+    );
+}
@@ -0,0 +1,445 @@
+//! CSV files for editing strings in spreadsheets.
+//!
+//! Columns are key, comment, tags and one per locale. Plural keys get a row per
+//! quantity, named `<key>:<quantity>`. Texts are written like in twine files.
+
+use anyhow::{anyhow, Ok, Result};
+use indexmap::IndexMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::exchange::{key_text, Conflict, ImportReport, TwineFiles};
+use crate::output::OutputFiles;
+use crate::parse::{Key, StringValue};
+use crate::pipeline::Source;
+use crate::plural;
+
+const KEY: &str = "key";
+const COMMENT: &str = "comment";
+const TAGS: &str = "tags";
+
+/// CSV with all keys of `sources`, `default_lang` is the first locale column
+pub fn export(sources: &[Source], default_lang: &str) -> Result<String> {
+    let keys: Vec<&Key> = sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys)
+        .collect();
+    let mut locales: Vec<String> = keys
+        .iter()
+        .flat_map(|key| &key.localizations)
+        .map(|str| str.language_code.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if let Some(index) = locales.iter().position(|locale| locale == default_lang) {
+        let default = locales.remove(index);
+        locales.insert(0, default);
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut header = vec![KEY, COMMENT, TAGS];
+    header.extend(locales.iter().map(String::as_str));
+    writer.write_record(&header)?;
+    for key in keys {
+        let comment = key.comment.clone().unwrap_or_default();
+        let tags = key.tags.join(",");
+        for quantity in row_quantities(key, &locales) {
+            let name = match &quantity {
+                Some(quantity) => format!("{}:{}", key.name, quantity),
+                None => key.name.clone(),
+            };
+            let mut record = vec![name, comment.clone(), tags.clone()];
+            record.extend(
+                locales
+                    .iter()
+                    .map(|locale| key_text(key, locale, &quantity).unwrap_or_default()),
+            );
+            writer.write_record(&record)?;
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// `None` for a single key, otherwise the quantities used by the key or by any of
+/// the `locales`, so translators have a row for every quantity they need
+fn row_quantities(key: &Key, locales: &[String]) -> Vec<Option<String>> {
+    let mut quantities: Vec<&str> = Vec::new();
+    for str in &key.localizations {
+        match &str.value {
+            StringValue::Single(_) => return vec![None],
+            StringValue::Plural {
+                quantities: plurals,
+            } => quantities.extend(plurals.iter().map(|plural| plural.quantity.as_str())),
+        }
+    }
+    quantities.extend(
+        locales
+            .iter()
+            .filter_map(|locale| plural::categories(locale))
+            .flatten(),
+    );
+    quantities.sort_by_key(|quantity| (plural::order(quantity), *quantity));
+    quantities.dedup();
+    quantities
+        .into_iter()
+        .map(|quantity| Some(quantity.to_string()))
+        .collect()
+}
+
+/// Row of a sheet, `cells` are keyed by the column name
+struct Row {
+    key: String,
+    quantity: Option<String>,
+    cells: HashMap<String, String>,
+}
+
+struct Sheet {
+    /// Name the sheet was read from, used in reports
+    name: String,
+    locales: Vec<String>,
+    rows: Vec<Row>,
+}
+
+impl Sheet {
+    fn read(path: &Path) -> Result<Sheet> {
+        let name = path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+        Sheet::parse(&mut reader, name)
+            .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))
+    }
+
+    fn parse<R: std::io::Read>(reader: &mut csv::Reader<R>, name: String) -> Result<Sheet> {
+        let header: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+        if !header.iter().any(|column| column == KEY) {
+            return Err(anyhow!("no \"{}\" column", KEY));
+        }
+        let locales = header
+            .iter()
+            .filter(|column| ![KEY, COMMENT, TAGS].contains(&column.as_str()))
+            .cloned()
+            .collect();
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let mut cells: HashMap<String, String> = header
+                .iter()
+                .cloned()
+                .zip(record?.iter().map(str::to_string))
+                .collect();
+            let name = cells.remove(KEY).unwrap_or_default();
+            if name.is_empty() {
+                continue;
+            }
+            let (key, quantity) = match name.rsplit_once(':') {
+                Some((key, quantity)) if plural::CATEGORIES.contains(&quantity) => {
+                    (key.to_string(), Some(quantity.to_string()))
+                }
+                _ => (name, None),
+            };
+            rows.push(Row {
+                key,
+                quantity,
+                cells,
+            });
+        }
+        Ok(Sheet {
+            name,
+            locales,
+            rows,
+        })
+    }
+
+    fn cell(&self, key: &str, quantity: &Option<String>, column: &str) -> Option<&str> {
+        self.rows
+            .iter()
+            .find(|row| row.key == key && row.quantity == *quantity)
+            .and_then(|row| row.cells.get(column))
+            .map(String::as_str)
+    }
+}
+
+/// Updates the twine files at `paths` with the sheet at `path` and adds keys which
+/// only exist in the sheet to the first file. Empty cells are ignored.
+///
+/// If `base` is the sheet as it was exported, cells changed in both the sheet and the
+/// sources since then are reported as conflicts, otherwise the sheet wins.
+/// Returns the changed files.
+pub fn import(
+    paths: &[PathBuf],
+    path: &Path,
+    base: Option<&Path>,
+) -> Result<(OutputFiles, ImportReport)> {
+    let sheet = Sheet::read(path)?;
+    let base = base.map(Sheet::read).transpose()?;
+    let mut files = TwineFiles::read(paths)?;
+    let mut report = ImportReport::default();
+
+    let mut keys: IndexMap<&str, Vec<&Row>> = IndexMap::new();
+    for row in &sheet.rows {
+        keys.entry(&row.key).or_default().push(row);
+    }
+    for (name, rows) in keys {
+        let is_plural = rows.iter().any(|row| row.quantity.is_some());
+        let conflict = |reason: String| Conflict {
+            origin: format!("{}: {}", sheet.name, name),
+            reason,
+        };
+        if files.find_key(name, !is_plural).is_some() && files.find_key(name, is_plural).is_none() {
+            let kind = if is_plural { "single" } else { "plural" };
+            report.conflicts.push(conflict(format!(
+                "\"{}\" is a {} key in the sources",
+                name, kind
+            )));
+            continue;
+        }
+        if files.find_key(name, is_plural).is_none() {
+            if base
+                .as_ref()
+                .is_some_and(|base| base.rows.iter().any(|row| row.key == name))
+            {
+                report.conflicts.push(conflict(format!(
+                    "\"{}\" was removed from the sources since export",
+                    name
+                )));
+                continue;
+            }
+            let attributes = new_key_attributes(&sheet, &rows);
+            if !attributes
+                .iter()
+                .any(|(name, _)| sheet.locales.contains(&locale_of(name)))
+            {
+                report
+                    .conflicts
+                    .push(conflict(format!("new key \"{}\" has no texts", name)));
+                continue;
+            }
+            files.add_key(name, &attributes)?;
+            report.created += 1;
+            continue;
+        }
+
+        let changes = {
+            let key = files.find_key(name, is_plural).unwrap();
+            cell_changes(&sheet, base.as_ref(), key, &rows)
+        };
+        for change in changes {
+            match change {
+                Change::Unchanged => report.unchanged += 1,
+                Change::Set { attribute, value } => {
+                    files.set(name, is_plural, &attribute, &value);
+                    report.updated += 1;
+                }
+                Change::Conflict { column, reason } => report.conflicts.push(Conflict {
+                    origin: format!("{}: {} {}", sheet.name, name, column),
+                    reason,
+                }),
+            }
+        }
+    }
+    Ok((files.into_changed(), report))
+}
+
+enum Change {
+    Unchanged,
+    Set { attribute: String, value: String },
+    Conflict { column: String, reason: String },
+}
+
+/// Changes of the non-empty cells of an existing key
+fn cell_changes(sheet: &Sheet, base: Option<&Sheet>, key: &Key, rows: &[&Row]) -> Vec<Change> {
+    let mut cells: Vec<Cell> = Vec::new();
+    for row in rows {
+        for locale in &sheet.locales {
+            if let Some(value) = row.cells.get(locale) {
+                cells.push(Cell {
+                    attribute: match &row.quantity {
+                        Some(quantity) => format!("{}:{}", locale, quantity),
+                        None => locale.clone(),
+                    },
+                    column: locale,
+                    row,
+                    value,
+                    current: key_text(key, locale, &row.quantity).unwrap_or_default(),
+                });
+            }
+        }
+    }
+    // Every row of a plural key repeats its comment and tags, the first one is taken
+    let comment = key.comment.clone().unwrap_or_default();
+    for (column, current) in [(COMMENT, comment), (TAGS, key.tags.join(","))] {
+        if let Some(value) = rows[0].cells.get(column) {
+            cells.push(Cell {
+                attribute: column.to_string(),
+                column,
+                row: rows[0],
+                value,
+                current,
+            });
+        }
+    }
+
+    cells
+        .into_iter()
+        .filter(|cell| !cell.value.is_empty())
+        .map(|cell| {
+            if cell.value == cell.current {
+                return Change::Unchanged;
+            }
+            let set = Change::Set {
+                attribute: cell.attribute.clone(),
+                value: cell.value.to_string(),
+            };
+            let Some(base) = base else {
+                return set;
+            };
+            let base = base
+                .cell(&key.name, &cell.row.quantity, cell.column)
+                .unwrap_or_default();
+            if cell.value == base {
+                // Only the sources changed since export
+                Change::Unchanged
+            } else if cell.current != base {
+                Change::Conflict {
+                    column: cell.attribute,
+                    reason: format!(
+                        "changed in both the sheet and the sources since export, sheet \"{}\", sources \"{}\"",
+                        cell.value, cell.current
+                    ),
+                }
+            } else {
+                set
+            }
+        })
+        .collect()
+}
+
+/// Non-empty cell of the sheet along with the current value in the sources
+struct Cell<'a> {
+    /// Twine attribute, e.g. "ru:few" or "comment"
+    attribute: String,
+    column: &'a str,
+    row: &'a Row,
+    value: &'a str,
+    current: String,
+}
+
+/// Comment, tags and texts grouped by locale, like they are written in twine files
+fn new_key_attributes(sheet: &Sheet, rows: &[&Row]) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    for column in [COMMENT, TAGS] {
+        if let Some(value) = rows[0].cells.get(column).filter(|value| !value.is_empty()) {
+            attributes.push((column.to_string(), value.clone()));
+        }
+    }
+    for locale in &sheet.locales {
+        for row in rows {
+            let Some(value) = row.cells.get(locale).filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            let name = match &row.quantity {
+                Some(quantity) => format!("{}:{}", locale, quantity),
+                None => locale.clone(),
+            };
+            attributes.push((name, value.clone()));
+        }
+    }
+    attributes
+}
+
+fn locale_of(attribute: &str) -> String {
+    attribute.split(':').next().unwrap_or(attribute).to_string()
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use assert_fs::prelude::*;
+
+#[cfg(test)]
+const TWINE: &str = "[[Main]]
+  [find]
+    en = Find
+    ru = Искать
+    comment = Toolbar button
+  [close]
+    en = Close
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+";
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn exports_a_row_per_key_and_quantity() -> Result<()> {
+    let sources = vec![crate::exchange::source(TWINE)];
+    let expected = "key,comment,tags,en,ru
+find,Toolbar button,,Find,Искать
+close,,,Close,
+songs:one,,,%d song,
+songs:few,,,,
+songs:many,,,,
+songs:other,,,%d songs,
+";
+    assert_eq!(export(&sources, "en")?, expected);
+    Ok(())
+}
+
+#[test]
+fn imports_changes_creating_keys_and_reporting_conflicts() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let twine = dir.child("strings.txt");
+    twine.write_str(TWINE)?;
+    let base = dir.child("base.csv");
+    base.write_str("key,comment,tags,en,ru\nfind,Toolbar button,,Find,Найти\nclose,,,Close,\n")?;
+    let sheet = dir.child("sheet.csv");
+    sheet.write_str(
+        "key,comment,tags,en,ru
+find,Toolbar button,,Find,Поиск
+close,Closes the window,,Close,Закрыть
+songs:few,,,,%d песни
+open:one,,,%d file,%d файл
+open:other,,,%d files,
+",
+    )?;
+
+    let (files, report) = import(&[twine.to_path_buf()], sheet.path(), Some(base.path()))?;
+
+    assert_eq!(
+        (report.updated, report.created, report.unchanged),
+        (3, 1, 3)
+    );
+    let conflicts: Vec<(&str, &str)> = report
+        .conflicts
+        .iter()
+        .map(|c| (c.origin.as_str(), c.reason.as_str()))
+        .collect();
+    assert_eq!(
+        conflicts,
+        vec![(
+            "sheet.csv: find ru",
+            "changed in both the sheet and the sources since export, sheet \"Поиск\", sources \"Искать\""
+        )]
+    );
+    let expected = "[[Main]]
+  [find]
+    en = Find
+    ru = Искать
+    comment = Toolbar button
+  [close]
+    en = Close
+    ru = Закрыть
+    comment = Closes the window
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:few = %d песни
+  [open]
+    en:one = %d file
+    en:other = %d files
+    ru:one = %d файл
+";
+    assert_eq!(files[twine.path()], expected);
+    Ok(())
+}
@@ -0,0 +1,347 @@
+//! Translation coverage of locales compared to the default language, in total and per source.
+
+use anyhow::{Ok, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::exchange::{self, ExportEntry};
+use crate::parse::File;
+use crate::pipeline::Source;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum StatsFormat {
+    /// Aligned columns for the terminal
+    Table,
+    Json,
+    /// Tables to post on merge requests
+    Markdown,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    pub default_lang: String,
+    /// Keys with a text in the default language
+    pub keys: usize,
+    pub locales: Vec<LocaleStats>,
+    pub sources: Vec<SourceStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SourceStats {
+    pub name: String,
+    pub keys: usize,
+    pub locales: Vec<LocaleStats>,
+}
+
+#[derive(Serialize, Default, PartialEq, Eq, Debug)]
+pub struct LocaleStats {
+    pub locale: String,
+    /// Keys with a text in the locale, plurals count even if quantities are missing
+    pub translated: usize,
+    /// Keys without a text in the locale
+    pub missing_keys: Vec<String>,
+    /// Translated plurals lacking quantities the locale uses
+    pub missing_quantities: Vec<MissingQuantities>,
+    /// Words of the default language texts still to translate
+    pub untranslated_words: usize,
+    pub untranslated_chars: usize,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct MissingQuantities {
+    pub key: String,
+    pub quantities: Vec<String>,
+}
+
+/// Counts the translations of every locale of `sources`
+pub fn collect(sources: &[Source], default_lang: &str) -> Result<Stats> {
+    let locales = exchange::target_locales(sources, default_lang, &[])?;
+    let sources: Vec<SourceStats> = sources
+        .iter()
+        .map(|source| {
+            let (keys, locales) = file_stats(&source.file, default_lang, &locales);
+            SourceStats {
+                name: source.name.clone(),
+                keys,
+                locales,
+            }
+        })
+        .collect();
+    let mut totals: Vec<LocaleStats> = locales
+        .iter()
+        .map(|locale| LocaleStats {
+            locale: locale.clone(),
+            ..Default::default()
+        })
+        .collect();
+    for source in &sources {
+        for (total, stats) in totals.iter_mut().zip(&source.locales) {
+            total.add(stats);
+        }
+    }
+    Ok(Stats {
+        default_lang: default_lang.to_string(),
+        keys: sources.iter().map(|source| source.keys).sum(),
+        locales: totals,
+        sources,
+    })
+}
+
+/// Number of keys of `file` in the default language and stats of each locale
+fn file_stats(file: &File, default_lang: &str, locales: &[String]) -> (usize, Vec<LocaleStats>) {
+    let keys = file
+        .sections
+        .iter()
+        .flat_map(|section| &section.keys)
+        .filter(|key| {
+            key.localizations
+                .iter()
+                .any(|str| str.language_code == default_lang)
+        })
+        .count();
+    let stats = locales
+        .iter()
+        .map(|locale| {
+            let entries = exchange::export_entries(file, default_lang, locale);
+            let mut stats = LocaleStats {
+                locale: locale.clone(),
+                ..Default::default()
+            };
+            for entry in entries {
+                stats.count(&entry);
+            }
+            stats
+        })
+        .collect();
+    (keys, stats)
+}
+
+impl LocaleStats {
+    fn count(&mut self, entry: &ExportEntry) {
+        let untranslated: Vec<_> = entry
+            .texts
+            .iter()
+            .filter(|text| text.target.is_none())
+            .collect();
+        for text in &untranslated {
+            self.untranslated_words += text.source.split_whitespace().count();
+            self.untranslated_chars += text.source.chars().count();
+        }
+        if untranslated.len() == entry.texts.len() {
+            self.missing_keys.push(entry.key.name.clone());
+            return;
+        }
+        self.translated += 1;
+        if !untranslated.is_empty() {
+            self.missing_quantities.push(MissingQuantities {
+                key: entry.key.name.clone(),
+                quantities: untranslated
+                    .iter()
+                    .filter_map(|text| text.quantity.clone())
+                    .collect(),
+            });
+        }
+    }
+
+    fn add(&mut self, other: &LocaleStats) {
+        self.translated += other.translated;
+        self.missing_keys.extend(other.missing_keys.iter().cloned());
+        self.missing_quantities
+            .extend(other.missing_quantities.iter().cloned());
+        self.untranslated_words += other.untranslated_words;
+        self.untranslated_chars += other.untranslated_chars;
+    }
+}
+
+const HEADERS: [&str; 6] = [
+    "Locale",
+    "Translated",
+    "Missing keys",
+    "Missing quantities",
+    "Words",
+    "Chars",
+];
+
+impl Stats {
+    pub fn render(&self, format: StatsFormat) -> Result<String> {
+        let mut result = String::new();
+        match format {
+            StatsFormat::Json => {
+                result = serde_json::to_string_pretty(self)?;
+                result.push('\n');
+            }
+            StatsFormat::Table => {
+                result.push_str(&format!(
+                    "All sources: {} keys in {}\n",
+                    self.keys, self.default_lang
+                ));
+                result.push_str(&table(&rows(self.keys, &self.locales)));
+                for source in &self.sources {
+                    result.push_str(&format!("\n{}: {} keys\n", source.name, source.keys));
+                    result.push_str(&table(&rows(source.keys, &source.locales)));
+                }
+            }
+            StatsFormat::Markdown => {
+                result.push_str(&format!(
+                    "### Translations\n\n{} keys in {}\n\n",
+                    self.keys, self.default_lang
+                ));
+                result.push_str(&markdown(&rows(self.keys, &self.locales)));
+                for source in &self.sources {
+                    result.push_str(&format!(
+                        "\n#### {}\n\n{} keys\n\n",
+                        source.name, source.keys
+                    ));
+                    result.push_str(&markdown(&rows(source.keys, &source.locales)));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn rows(keys: usize, locales: &[LocaleStats]) -> Vec<[String; 6]> {
+    locales
+        .iter()
+        .map(|stats| {
+            let percent = match keys {
+                0 => 100.0,
+                _ => stats.translated as f64 * 100.0 / keys as f64,
+            };
+            [
+                stats.locale.clone(),
+                format!("{} ({:.0}%)", stats.translated, percent.floor()),
+                stats.missing_keys.len().to_string(),
+                stats
+                    .missing_quantities
+                    .iter()
+                    .map(|missing| missing.quantities.len())
+                    .sum::<usize>()
+                    .to_string(),
+                stats.untranslated_words.to_string(),
+                stats.untranslated_chars.to_string(),
+            ]
+        })
+        .collect()
+}
+
+fn table(rows: &[[String; 6]]) -> String {
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: [&str; 6]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        format!("{}\n", cells.join("  ").trim_end())
+    };
+    let mut result = line(HEADERS);
+    for row in rows {
+        result.push_str(&line(row.each_ref().map(String::as_str)));
+    }
+    result
+}
+
+fn markdown(rows: &[[String; 6]]) -> String {
+    let mut result = format!("| {} |\n", HEADERS.join(" | "));
+    result.push_str("|---|---:|---:|---:|---:|---:|\n");
+    for row in rows {
+        result.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    result
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn source(name: &str, text: &str) -> Source {
+    let dir = assert_fs::TempDir::new().unwrap();
+    let path = dir.path().join(format!("{}.txt", name));
+    std::fs::write(&path, text).unwrap();
+    Source {
+        name: name.to_string(),
+        file: crate::parse::parse(&path).unwrap(),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn counts_missing_keys_quantities_and_words() -> Result<()> {
+    let sources = [
+        source(
+            "main",
+            "[[Main]]
+  [title]
+    en = Hello world
+    ru = Привет, мир
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+    ru:other = %d песен
+",
+        ),
+        source(
+            "settings",
+            "[[Settings]]
+  [save]
+    en = Save changes
+    de = Speichern
+",
+        ),
+    ];
+    let stats = collect(&sources, "en")?;
+    assert_eq!(stats.keys, 3);
+    let locales: Vec<&str> = stats.locales.iter().map(|s| s.locale.as_str()).collect();
+    assert_eq!(locales, vec!["de", "ru"]);
+    let ru = &stats.locales[1];
+    assert_eq!(ru.translated, 2);
+    assert_eq!(ru.missing_keys, vec!["save"]);
+    // Russian uses "few" and "many", their text comes from "other"
+    assert_eq!(
+        ru.missing_quantities,
+        vec![MissingQuantities {
+            key: "songs".to_string(),
+            quantities: vec!["few".to_string(), "many".to_string()],
+        }]
+    );
+    assert_eq!(ru.untranslated_words, 2 + 2 + 2);
+    assert_eq!(ru.untranslated_chars, 12 + 8 + 8);
+    assert_eq!(
+        stats.sources[0].locales[0].missing_keys,
+        vec!["title", "songs"]
+    );
+    Ok(())
+}
+
+#[test]
+fn renders_tables() -> Result<()> {
+    let sources = [source(
+        "main",
+        "[[Main]]
+  [title]
+    en = Hello world
+    ru = Привет, мир
+  [ok]
+    en = OK
+",
+    )];
+    let stats = collect(&sources, "en")?;
+    let expected = "All sources: 2 keys in en
+Locale  Translated  Missing keys  Missing quantities  Words  Chars
+ru      1 (50%)     1             0                   1      2
+
+main: 2 keys
+Locale  Translated  Missing keys  Missing quantities  Words  Chars
+ru      1 (50%)     1             0                   1      2
+";
+    assert_eq!(stats.render(StatsFormat::Table)?, expected);
+    assert!(stats
+        .render(StatsFormat::Markdown)?
+        .contains("| ru | 1 (50%) | 1 | 0 | 1 | 2 |\n"));
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use anyhow::{Ok, Result};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+
+use crate::escape;
+use crate::generator::{self, Generator};
+use crate::ios_gen;
+use crate::kotlin_gen::{capitalize, words};
+use crate::output::OutputFiles;
+use crate::parse::{self, Key, Placeholder, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Swift keywords which can't be property or function names without backticks
+const KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "continue",
+    "default",
+    "defer",
+    "do",
+    "else",
+    "enum",
+    "extension",
+    "fallthrough",
+    "false",
+    "for",
+    "func",
+    "guard",
+    "if",
+    "import",
+    "in",
+    "init",
+    "inout",
+    "internal",
+    "is",
+    "let",
+    "nil",
+    "operator",
+    "private",
+    "protocol",
+    "public",
+    "repeat",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "subscript",
+    "super",
+    "switch",
+    "throw",
+    "throws",
+    "true",
+    "try",
+    "typealias",
+    "var",
+    "where",
+    "while",
+];
+
+/// Writes `L10n.swift` next to the `.lproj` directories, an `enum L10n` with a property or
+/// function per key of all sources. Key names are split on "_" and "." into nested enums,
+/// e.g. "settings.title" becomes `L10n.Settings.title`. Arguments are typed after the
+/// placeholders, plurals take the quantity first and are looked up in the `.stringsdict`.
+pub struct SwiftGenerator;
+
+impl Generator for SwiftGenerator {
+    fn name(&self) -> &str {
+        "swift"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let keys = sources
+            .iter()
+            .flat_map(|source| &source.file.sections)
+            .flat_map(|section| &section.keys);
+        let contents = render(keys, ios_gen::table_name(options), &options.default_lang);
+        Ok(OutputFiles::from([(
+            options.output_dir.join("L10n.swift"),
+            contents,
+        )]))
+    }
+}
+
+/// Enum of a namespace, members are written before nested enums
+#[derive(Default)]
+struct Namespace {
+    /// Rendered members by name, with whether they are plurals
+    members: IndexMap<String, (bool, String)>,
+    children: IndexMap<String, Namespace>,
+}
+
+impl Namespace {
+    fn write(&self, result: &mut String, name: &str, depth: usize) {
+        let indent = "    ".repeat(depth);
+        result.push_str(&format!("{}enum {} {{\n", indent, name));
+        for (index, (_, member)) in self.members.values().enumerate() {
+            if index > 0 {
+                result.push('\n');
+            }
+            for line in member.lines() {
+                result.push_str(&format!("{}    {}\n", indent, line));
+            }
+        }
+        for (index, (name, child)) in self.children.iter().enumerate() {
+            if index > 0 || !self.members.is_empty() {
+                result.push('\n');
+            }
+            child.write(result, name, depth + 1);
+        }
+        result.push_str(&format!("{}}}\n", indent));
+    }
+}
+
+/// Renders the Swift file with accessors of `keys`. Of keys with the same name the first
+/// one wins, but a plural one replaces a single one like the `.stringsdict` does on iOS.
+pub fn render<'a>(
+    keys: impl IntoIterator<Item = &'a Key>,
+    table: &str,
+    default_lang: &Option<String>,
+) -> String {
+    let mut root = Namespace::default();
+    for key in keys {
+        let Some(value) = generator::typed_value(key, default_lang) else {
+            continue;
+        };
+        let mut path = words(&key.name);
+        let Some(last) = path.pop() else {
+            continue;
+        };
+        let namespace = path.iter().fold(&mut root, |namespace, part| {
+            namespace.children.entry(capitalize(part)).or_default()
+        });
+        let is_plural = matches!(value, StringValue::Plural { .. });
+        let name = escape_name(&member_name(last));
+        match namespace.members.get(&name) {
+            Some((true, _)) => continue,
+            Some((false, _)) if !is_plural => continue,
+            _ => (),
+        }
+        let member = render_member(key, value, &name);
+        namespace.members.insert(name, (is_plural, member));
+    }
+
+    let mut result = String::new();
+    result.push_str("// Generated by utas, do not edit\n\n");
+    result.push_str("import Foundation\n\n");
+    root.write(&mut result, "L10n", 0);
+    result.push('\n');
+    result.push_str("extension L10n {\n");
+    result.push_str(
+        "    fileprivate static func tr(_ key: String, _ args: CVarArg...) -> String {\n",
+    );
+    result.push_str(&format!(
+        "        let format = NSLocalizedString(key, tableName: \"{}\", bundle: .main, comment: \"\")\n",
+        escape::swift(table)
+    ));
+    result.push_str(
+        "        return String(format: format, locale: Locale.current, arguments: args)\n",
+    );
+    result.push_str("    }\n");
+    result.push_str("}\n");
+    result
+}
+
+fn render_member(key: &Key, value: &StringValue, name: &str) -> String {
+    let (texts, is_plural) = match value {
+        StringValue::Single(text) => (vec![text.as_str()], false),
+        StringValue::Plural { quantities } => (
+            quantities
+                .iter()
+                .map(|plural| plural.text.as_str())
+                .collect(),
+            true,
+        ),
+    };
+    let mut arguments = arguments(&texts);
+    if is_plural {
+        // The stringsdict format "%#@value@" takes the quantity as the first argument
+        arguments.insert(1, "Int");
+    }
+    let arguments: Vec<(String, &str)> = arguments
+        .into_iter()
+        .map(|(position, kind)| match position {
+            1 if is_plural => ("count".to_string(), kind),
+            _ => (format!("p{}", position), kind),
+        })
+        .collect();
+    let literal = format!("\"{}\"", escape::swift(&key.name));
+
+    let mut result = String::new();
+    if let Some(comment) = &key.comment {
+        result.push_str(&format!("/// {}\n", comment));
+    }
+    if arguments.is_empty() {
+        result.push_str(&format!(
+            "static var {}: String {{\n    L10n.tr({})\n}}\n",
+            name, literal
+        ));
+        return result;
+    }
+    let parameters: Vec<String> = arguments
+        .iter()
+        .map(|(name, kind)| format!("_ {}: {}", name, kind))
+        .collect();
+    let mut call = vec![literal];
+    call.extend(arguments.into_iter().map(|(name, _)| name));
+    result.push_str(&format!(
+        "static func {}({}) -> String {{\n    L10n.tr({})\n}}\n",
+        name,
+        parameters.join(", "),
+        call.join(", ")
+    ));
+    result
+}
+
+/// Swift types of the format arguments by their position, the first placeholder
+/// of a position decides. Positions no placeholder takes are `CVarArg`.
+fn arguments(texts: &[&str]) -> BTreeMap<usize, &'static str> {
+    let mut result = BTreeMap::new();
+    for text in texts {
+        for placeholder in parse::placeholders(text) {
+            result
+                .entry(placeholder.position)
+                .or_insert_with(|| swift_type(&placeholder));
+        }
+    }
+    let last = result.keys().last().copied().unwrap_or(0);
+    for position in 1..last {
+        result.entry(position).or_insert("CVarArg");
+    }
+    result
+}
+
+fn swift_type(placeholder: &Placeholder) -> &'static str {
+    match placeholder.conversion {
+        'u' => "UInt",
+        'd' | 'i' | 'x' | 'X' | 'o' => "Int",
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => "Double",
+        'c' => "CChar",
+        's' | 'S' | '@' => "String",
+        _ => "CVarArg",
+    }
+}
+
+/// Member name of the last part of a key, e.g. "title" for "Title"
+fn member_name(word: &str) -> String {
+    let mut chars = word.chars();
+    let name: String = chars
+        .next()
+        .map(|c| c.to_lowercase().chain(chars).collect())
+        .unwrap_or_default();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+fn escape_name(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("`{}`", name),
+        false => name.to_string(),
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::LocalizedString;
+
+#[cfg(test)]
+fn key(name: &str, text: &str) -> Key {
+    Key {
+        name: name.to_string(),
+        comment: None,
+        tags: Vec::new(),
+        localizations: vec![LocalizedString {
+            language_code: "en".to_string(),
+            value: StringValue::Single(text.to_string()),
+        }],
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn types_arguments_after_placeholders() {
+    let types = |text| arguments(&[text]).into_values().collect::<Vec<_>>();
+    assert_eq!(
+        types("%1$s has %2$d of %3$.2f"),
+        vec!["String", "Int", "Double"]
+    );
+    assert_eq!(types("%2$u left"), vec!["CVarArg", "UInt"]);
+    assert_eq!(types("100%% done"), Vec::<&str>::new());
+}
+
+#[test]
+fn nests_keys_into_namespaces() {
+    let keys = [
+        key("login_title", "Login"),
+        key("login.screen_hint", "Hint for %s"),
+        key("login_title", "Duplicate"),
+    ];
+    let rendered = render(&keys, "Localizable", &None);
+    let expected = "enum L10n {
+    enum Login {
+        static var title: String {
+            L10n.tr(\"login_title\")
+        }
+
+        enum Screen {
+            static func hint(_ p1: String) -> String {
+                L10n.tr(\"login.screen_hint\", p1)
+            }
+        }
+    }
+}
+";
+    assert!(rendered.contains(expected), "{}", rendered);
+    assert!(!rendered.contains("Duplicate"));
+}
@@ -0,0 +1,411 @@
+use anyhow::{anyhow, Ok, Result};
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::escape;
+use crate::generator::{GenResult, Generator, Line};
+use crate::output::OutputFiles;
+use crate::parse::{self, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Placeholder of the output path replaced with the locale code
+const LOCALE_PLACEHOLDER: &str = "{locale}";
+
+/// Renders a user supplied minijinja template.
+///
+/// If the output path contains `{locale}` the template is rendered once per locale,
+/// otherwise once for all locales.
+pub struct TemplateGenerator {
+    name: String,
+    source: String,
+    output: String,
+}
+
+impl TemplateGenerator {
+    /// `name` is used in error messages, `output` is relative to the output directory
+    pub fn new(name: String, source: String, output: String) -> TemplateGenerator {
+        TemplateGenerator {
+            name,
+            source,
+            output,
+        }
+    }
+
+    pub fn from_file(path: &Path, output: String) -> Result<TemplateGenerator> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Cannot read template {}: {}", path.display(), err))?;
+        Ok(TemplateGenerator::new(
+            path.display().to_string(),
+            source,
+            output,
+        ))
+    }
+
+    fn per_locale(&self) -> bool {
+        self.output.contains(LOCALE_PLACEHOLDER)
+    }
+
+    fn environment(&self) -> Result<Environment<'_>> {
+        let mut env = Environment::new();
+        // Texts are only escaped by the filters, whatever the template file is named
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_keep_trailing_newline(true);
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.add_filter("xml", |text: &str| escape::xml(text));
+        env.add_filter("json", |text: &str| escape::json(text));
+        env.add_filter("strings", |text: &str| escape::strings(text));
+        env.add_filter("kotlin", |text: &str| escape::kotlin(text));
+        env.add_filter("swift", |text: &str| escape::swift(text));
+        env.add_template(&self.name, &self.source)
+            .map_err(|err| anyhow!("Invalid template {}: {:#}", self.name, err))?;
+        Ok(env)
+    }
+}
+
+impl Generator for TemplateGenerator {
+    fn name(&self) -> &str {
+        "template"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let mut generated = GenResult::from_files(sources.iter().map(|src| &src.file))?;
+        generated.fill_absent_translations(&options.default_lang);
+        let env = self.environment()?;
+        let template = env.get_template(&self.name)?;
+        let render_err =
+            |err: minijinja::Error| anyhow!("Failed to render {}: {:#}", self.name, err);
+
+        let locales: Vec<LocaleContext> = generated
+            .value
+            .iter()
+            .map(|(locale, lines)| LocaleContext {
+                code: &locale.value,
+                strings: lines.value.iter().map(string_context).collect(),
+            })
+            .collect();
+        let codes: Vec<&str> = locales.iter().map(|locale| locale.code).collect();
+        let default_lang = options.default_lang.as_deref();
+        let file_name = options.file_name.as_deref();
+
+        if self.per_locale() {
+            locales
+                .par_iter()
+                .map(|locale| {
+                    let contents = template
+                        .render(PerLocaleContext {
+                            locale: locale.code,
+                            strings: &locale.strings,
+                            locales: &codes,
+                            default_lang,
+                            file_name,
+                        })
+                        .map_err(render_err)?;
+                    let path = self.output.replace(LOCALE_PLACEHOLDER, locale.code);
+                    Ok((options.output_dir.join(path), contents))
+                })
+                .collect()
+        } else {
+            let contents = template
+                .render(AllLocalesContext {
+                    keys: key_contexts(sources),
+                    locales: &locales,
+                    default_lang,
+                    file_name,
+                })
+                .map_err(render_err)?;
+            Ok(OutputFiles::from([(
+                options.output_dir.join(&self.output),
+                contents,
+            )]))
+        }
+    }
+}
+
+/// Context of a template rendered for a single locale
+#[derive(Serialize)]
+struct PerLocaleContext<'a> {
+    locale: &'a str,
+    strings: &'a [StringContext<'a>],
+    /// Codes of all locales
+    locales: &'a [&'a str],
+    default_lang: Option<&'a str>,
+    file_name: Option<&'a str>,
+}
+
+/// Context of a template rendered once for all locales
+#[derive(Serialize)]
+struct AllLocalesContext<'a> {
+    keys: Vec<KeyContext<'a>>,
+    locales: &'a [LocaleContext<'a>],
+    default_lang: Option<&'a str>,
+    file_name: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct LocaleContext<'a> {
+    code: &'a str,
+    strings: Vec<StringContext<'a>>,
+}
+
+/// Key with its strings in every locale, in the order of the sources
+#[derive(Serialize)]
+struct KeyContext<'a> {
+    name: &'a str,
+    comment: Option<&'a str>,
+    tags: &'a [String],
+    localizations: BTreeMap<&'a str, StringContext<'a>>,
+}
+
+#[derive(Serialize)]
+struct StringContext<'a> {
+    name: &'a str,
+    comment: Option<&'a str>,
+    tags: &'a [String],
+    plural: bool,
+    /// Text as written in the source, not set for plurals
+    text: Option<String>,
+    /// Text escaped for Android resources, not set for plurals
+    android_text: Option<&'a str>,
+    plurals: Vec<PluralContext<'a>>,
+    placeholders: Vec<PlaceholderContext>,
+}
+
+#[derive(Serialize)]
+struct PluralContext<'a> {
+    quantity: &'a str,
+    text: String,
+    android_text: &'a str,
+}
+
+#[derive(Serialize)]
+struct PlaceholderContext {
+    position: usize,
+    conversion: char,
+    spec: String,
+}
+
+fn key_contexts(sources: &[Source]) -> Vec<KeyContext<'_>> {
+    sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys)
+        .map(|key| KeyContext {
+            name: &key.name,
+            comment: key.comment.as_deref(),
+            tags: &key.tags,
+            localizations: key
+                .localizations
+                .iter()
+                .map(|str| {
+                    let line = Line {
+                        name: &key.name,
+                        comment: key.comment.as_deref(),
+                        tags: &key.tags,
+                        value: &str.value,
+                    };
+                    (str.language_code.as_str(), string_context(&line))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn string_context<'a>(line: &Line<'a>) -> StringContext<'a> {
+    let (text, android_text, plurals) = match line.value {
+        StringValue::Single(text) => (Some(parse::unescape(text)), Some(text.as_str()), vec![]),
+        StringValue::Plural { quantities } => (
+            None,
+            None,
+            quantities
+                .iter()
+                .map(|plural| PluralContext {
+                    quantity: &plural.quantity,
+                    text: parse::unescape(&plural.text),
+                    android_text: &plural.text,
+                })
+                .collect(),
+        ),
+    };
+    StringContext {
+        name: line.name,
+        comment: line.comment,
+        tags: line.tags,
+        plural: matches!(line.value, StringValue::Plural { .. }),
+        text,
+        android_text,
+        plurals,
+        placeholders: placeholder_contexts(line.value),
+    }
+}
+
+/// Placeholders of all plural quantities are merged, each argument is listed once
+fn placeholder_contexts(value: &StringValue) -> Vec<PlaceholderContext> {
+    let mut placeholders: Vec<parse::Placeholder> = match value {
+        StringValue::Single(text) => parse::placeholders(text),
+        StringValue::Plural { quantities } => quantities
+            .iter()
+            .flat_map(|plural| parse::placeholders(&plural.text))
+            .collect(),
+    };
+    placeholders.sort_by_key(|placeholder| placeholder.position);
+    placeholders.dedup_by_key(|placeholder| placeholder.position);
+    placeholders
+        .into_iter()
+        .map(|placeholder| PlaceholderContext {
+            position: placeholder.position,
+            conversion: placeholder.conversion,
+            spec: placeholder.spec,
+        })
+        .collect()
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{File, Key, LocalizedString, PluralValue, Section};
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+fn source(keys: Vec<Key>) -> Vec<Source> {
+    vec![Source {
+        name: "strings".to_string(),
+        file: File {
+            sections: vec![Section { name: None, keys }],
+        },
+    }]
+}
+
+#[cfg(test)]
+fn localized(lang: &str, value: StringValue) -> LocalizedString {
+    LocalizedString {
+        language_code: lang.to_string(),
+        value,
+    }
+}
+
+#[cfg(test)]
+fn sample_keys() -> Vec<Key> {
+    vec![
+        Key {
+            name: "greeting".to_string(),
+            comment: Some("Shown on start".to_string()),
+            tags: vec!["ios".to_string()],
+            localizations: vec![
+                localized(
+                    "en",
+                    StringValue::Single("Hi \\\"%1$s\\\" &amp; %2$d".to_string()),
+                ),
+                localized("ru", StringValue::Single("Привет".to_string())),
+            ],
+        },
+        Key {
+            name: "songs".to_string(),
+            comment: None,
+            tags: Vec::new(),
+            localizations: vec![localized(
+                "en",
+                StringValue::Plural {
+                    quantities: vec![
+                        PluralValue {
+                            quantity: "one".to_string(),
+                            text: "%d song".to_string(),
+                        },
+                        PluralValue {
+                            quantity: "other".to_string(),
+                            text: "%d songs".to_string(),
+                        },
+                    ],
+                },
+            )],
+        },
+    ]
+}
+
+#[cfg(test)]
+fn options(default_lang: Option<&str>) -> GenOptions {
+    GenOptions {
+        output_dir: PathBuf::from("out"),
+        default_lang: default_lang.map(str::to_string),
+        file_name: None,
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn renders_file_per_locale() -> Result<()> {
+    let template = r#"{{ locale }}:
+{% for s in strings %}
+{% if s.plural %}
+{{ s.name }}:
+{% for p in s.plurals %}
+  {{ p.quantity }}={{ p.text }}
+{% endfor %}
+{% else %}
+{{ s.name }}="{{ s.text | swift }}" {{ s.placeholders | map(attribute="conversion") | join(",") }}
+{% endif %}
+{% endfor %}
+"#;
+    let generator = TemplateGenerator::new(
+        "test".to_string(),
+        template.to_string(),
+        "{locale}/strings.txt".to_string(),
+    );
+    let actual = generator.render(&source(sample_keys()), &options(Some("en")))?;
+    let expected = OutputFiles::from([
+        (
+            PathBuf::from("out/en/strings.txt"),
+            "en:\ngreeting=\"Hi \\\"%1$s\\\" & %2$d\" s,d\nsongs:\n  one=%d song\n  other=%d songs\n"
+                .to_string(),
+        ),
+        (
+            PathBuf::from("out/ru/strings.txt"),
+            "ru:\ngreeting=\"Привет\" \nsongs:\n  one=%d song\n  other=%d songs\n".to_string(),
+        ),
+    ]);
+    assert_eq!(expected, actual);
+    Ok(())
+}
+
+#[test]
+fn renders_single_file_with_all_locales() -> Result<()> {
+    let template = r#"{% for key in keys %}
+{{ key.name }} # {{ key.comment or "" }} [{{ key.tags | join(",") }}]
+{% for code, s in key.localizations | items %}
+  {{ code }}={{ s.android_text if not s.plural else s.plurals | length }}
+{% endfor %}
+{% endfor %}
+{{ locales | map(attribute="code") | join(",") }}
+"#;
+    let generator = TemplateGenerator::new(
+        "test".to_string(),
+        template.to_string(),
+        "all.txt".to_string(),
+    );
+    let actual = generator.render(&source(sample_keys()), &options(None))?;
+    let expected = OutputFiles::from([(
+        PathBuf::from("out/all.txt"),
+        "greeting # Shown on start [ios]\n  en=Hi \\\"%1$s\\\" &amp; %2$d\n  ru=Привет\n\
+         songs #  []\n  en=2\n\
+         en,ru\n"
+            .to_string(),
+    )]);
+    assert_eq!(expected, actual);
+    Ok(())
+}
+
+#[test]
+fn fails_on_undefined_variable() {
+    let generator = TemplateGenerator::new(
+        "test".to_string(),
+        "{{ unknown }}".to_string(),
+        "all.txt".to_string(),
+    );
+    let result = generator.render(&source(sample_keys()), &options(None));
+    assert!(result.unwrap_err().to_string().contains("test"));
+}
@@ -0,0 +1,215 @@
+use anyhow::{Ok, Result};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+
+use crate::escape;
+use crate::generator::{self, Generator};
+use crate::output::OutputFiles;
+use crate::parse::{self, Key, Placeholder, StringValue};
+use crate::pipeline::{GenOptions, Source};
+use crate::web_gen::{self, PlaceholderStyle, PluralStyle, COUNT};
+
+/// Writes `translations.d.ts` declaring the keys of all sources and the parameters
+/// each of them takes, named like the "web" platform writes them into the JSON files
+#[derive(Default)]
+pub struct TypesGenerator {
+    pub plurals: PluralStyle,
+    pub placeholders: PlaceholderStyle,
+}
+
+impl Generator for TypesGenerator {
+    fn name(&self) -> &str {
+        "typescript"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let keys = sources
+            .iter()
+            .flat_map(|source| &source.file.sections)
+            .flat_map(|section| &section.keys);
+        let contents = self.render_types(keys, &options.default_lang);
+        Ok(OutputFiles::from([(
+            options.output_dir.join("translations.d.ts"),
+            contents,
+        )]))
+    }
+}
+
+/// Parameters of a key, positional ones are passed as an array
+#[derive(Default)]
+struct Params {
+    named: IndexMap<String, &'static str>,
+    indexed: BTreeMap<usize, &'static str>,
+}
+
+impl TypesGenerator {
+    /// Renders the module, of keys with the same name the first one wins,
+    /// but a plural one replaces a single one as it has the same `t()` key
+    pub fn render_types<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a Key>,
+        default_lang: &Option<String>,
+    ) -> String {
+        let mut params: IndexMap<&str, (bool, String)> = IndexMap::new();
+        for key in keys {
+            let Some(value) = generator::typed_value(key, default_lang) else {
+                continue;
+            };
+            let is_plural = matches!(value, StringValue::Plural { .. });
+            match params.get(key.name.as_str()) {
+                Some((true, _)) => continue,
+                Some((false, _)) if !is_plural => continue,
+                _ => (),
+            }
+            params.insert(&key.name, (is_plural, self.params(value).render()));
+        }
+
+        let mut result = String::new();
+        result.push_str("// Generated by utas, do not edit\n\n");
+        result.push_str("export type TranslationKey =\n");
+        let names: Vec<String> = params
+            .keys()
+            .map(|name| format!("  | \"{}\"", escape::json(name)))
+            .collect();
+        match names.is_empty() {
+            true => result.push_str("  never"),
+            false => result.push_str(&names.join("\n")),
+        }
+        result.push_str(";\n\n");
+        result.push_str("export interface TranslationParams {\n");
+        for (name, (_, params)) in &params {
+            result.push_str(&format!("  \"{}\": {};\n", escape::json(name), params));
+        }
+        result.push_str("}\n\n");
+        result.push_str(
+            "/** Arguments of `t()` for a key, the parameters are left out if it takes none */\n",
+        );
+        result.push_str("export type TranslationArgs<K extends TranslationKey> =\n");
+        result.push_str("  TranslationParams[K] extends undefined\n");
+        result.push_str("    ? [key: K]\n");
+        result.push_str("    : [key: K, params: TranslationParams[K]];\n");
+        result
+    }
+
+    /// Parameters as the "web" platform names the placeholders, plurals always need the count
+    fn params(&self, value: &StringValue) -> Params {
+        let (texts, count_position) = match value {
+            StringValue::Single(text) => (vec![text.as_str()], None),
+            StringValue::Plural { quantities } => (
+                quantities
+                    .iter()
+                    .map(|plural| plural.text.as_str())
+                    .collect(),
+                web_gen::count_position(quantities),
+            ),
+        };
+        let mut params = Params::default();
+        let is_plural = matches!(value, StringValue::Plural { .. });
+        // ICU plurals with positional placeholders select by the count's index, others by "count"
+        let indexed_count =
+            self.plurals == PluralStyle::Icu && self.placeholders == PlaceholderStyle::Indexed;
+        if is_plural && !(indexed_count && count_position.is_some()) {
+            params.named.insert(COUNT.to_string(), "number");
+        }
+        for text in texts {
+            for placeholder in parse::placeholders(text) {
+                let position = placeholder.position;
+                if count_position == Some(position) && !indexed_count {
+                    continue;
+                }
+                let kind = ts_type(&placeholder);
+                match self.placeholders {
+                    PlaceholderStyle::Named => {
+                        params
+                            .named
+                            .entry(format!("arg{}", position))
+                            .or_insert(kind);
+                    }
+                    PlaceholderStyle::Indexed => {
+                        params.indexed.entry(position - 1).or_insert(kind);
+                    }
+                }
+            }
+        }
+        params
+    }
+}
+
+impl Params {
+    fn render(&self) -> String {
+        let named: Vec<String> = self
+            .named
+            .iter()
+            .map(|(name, kind)| format!("{}: {}", name, kind))
+            .collect();
+        let named = format!("{{ {} }}", named.join("; "));
+        let last = self.indexed.keys().last().map_or(0, |index| index + 1);
+        let indexed: Vec<&str> = (0..last)
+            .map(|index| self.indexed.get(&index).copied().unwrap_or("unknown"))
+            .collect();
+        let indexed = format!("[{}]", indexed.join(", "));
+        match (self.named.is_empty(), self.indexed.is_empty()) {
+            (true, true) => "undefined".to_string(),
+            (false, true) => named,
+            (true, false) => indexed,
+            (false, false) => format!("{} & {}", indexed, named),
+        }
+    }
+}
+
+fn ts_type(placeholder: &Placeholder) -> &'static str {
+    match placeholder.conversion {
+        'd' | 'i' | 'u' | 'x' | 'X' | 'o' | 'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => {
+            "number"
+        }
+        's' | 'S' | 'c' => "string",
+        _ => "unknown",
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::PluralValue;
+
+#[cfg(test)]
+fn songs() -> StringValue {
+    let plural = |quantity: &str, text: &str| PluralValue {
+        quantity: quantity.to_string(),
+        text: text.to_string(),
+    };
+    StringValue::Plural {
+        quantities: vec![
+            plural("one", "%1$d song by %2$s"),
+            plural("other", "%1$d songs by %2$s"),
+        ],
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn names_params_like_the_web_platform() {
+    let params = |plurals, placeholders, value: &StringValue| {
+        TypesGenerator {
+            plurals,
+            placeholders,
+        }
+        .params(value)
+        .render()
+    };
+    use PlaceholderStyle::{Indexed, Named};
+    use PluralStyle::{I18next, Icu};
+    assert_eq!(
+        params(I18next, Named, &songs()),
+        "{ count: number; arg2: string }"
+    );
+    assert_eq!(params(Icu, Indexed, &songs()), "[number, string]");
+    // i18next passes the count by name even if other placeholders are positional
+    assert_eq!(
+        params(I18next, Indexed, &songs()),
+        "[unknown, string] & { count: number }"
+    );
+    let text = StringValue::Single("%2$.1f%% of %1$s".to_string());
+    assert_eq!(params(I18next, Indexed, &text), "[string, number]");
+    let text = StringValue::Single("100%% done".to_string());
+    assert_eq!(params(I18next, Named, &text), "undefined");
+}
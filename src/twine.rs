@@ -0,0 +1,669 @@
+//! Line based editing of twine files which keeps their layout, comments and key order,
+//! formatting them into the canonical layout and writing new ones from the parsed model.
+//!
+//! Only editing keeps the key order, formatting sorts keys by name.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::parse::{self, File, Key, StringValue};
+use crate::plural;
+
+/// Twine file as a list of lines, only touched lines change when it's written back
+pub struct TwineDocument {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+/// Lines of a `[key]` block, `end` is exclusive
+struct Block<'a> {
+    name: &'a str,
+    header: usize,
+    end: usize,
+}
+
+/// `#` comment lines are kept with the line they precede
+struct Commented<T> {
+    comments: Vec<String>,
+    item: T,
+}
+
+struct FormatKey {
+    name: String,
+    attributes: Vec<Commented<String>>,
+}
+
+struct FormatSection {
+    /// `None` for keys before the first `[[section]]`
+    header: Option<Commented<String>>,
+    /// Attributes outside of keys, they are kept right after the header
+    loose: Vec<Commented<String>>,
+    keys: Vec<Commented<FormatKey>>,
+}
+
+impl TwineDocument {
+    pub fn parse(text: &str) -> TwineDocument {
+        TwineDocument {
+            lines: text.lines().map(str::to_string).collect(),
+            trailing_newline: text.ends_with('\n') || text.is_empty(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<TwineDocument> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+        Ok(TwineDocument::parse(&text))
+    }
+
+    /// Sets `attribute` of `key`, a new attribute goes after the ones of the same locale.
+    /// Returns false if there is no such key.
+    pub fn set(&mut self, key: &str, attribute: &str, value: &str) -> bool {
+        let Some(block) = self.find_block(key, attribute) else {
+            return false;
+        };
+        let (header, end) = (block.header, block.end);
+        let value = write_value(value);
+        let attributes: Vec<(usize, String)> = (header + 1..end)
+            .filter_map(|index| {
+                attribute_line(&self.lines[index]).map(|(name, _)| (index, name.to_string()))
+            })
+            .collect();
+
+        if let Some((index, _)) = attributes.iter().find(|(_, name)| name == attribute) {
+            let indent = indentation(&self.lines[*index]).to_string();
+            self.lines[*index] = format!("{}{} = {}", indent, attribute, value);
+            return true;
+        }
+
+        let locale = attribute.split(':').next().unwrap_or(attribute);
+        let anchor = attributes
+            .iter()
+            .rev()
+            .find(|(_, name)| name.split(':').next() == Some(locale))
+            .or(attributes.last())
+            .map(|(index, _)| *index);
+        let (index, indent) = match anchor {
+            Some(index) => (index + 1, indentation(&self.lines[index]).to_string()),
+            None => (
+                header + 1,
+                format!("{}  ", indentation(&self.lines[header])),
+            ),
+        };
+        self.lines
+            .insert(index, format!("{}{} = {}", indent, attribute, value));
+        true
+    }
+
+    /// Appends a `[key]` block with `attributes` to the end of the last section,
+    /// indented like the last existing key
+    pub fn add_key(&mut self, key: &str, attributes: &[(String, String)]) {
+        let blocks = self.blocks();
+        let (header_indent, attribute_indent) = match blocks.last() {
+            Some(block) => {
+                let header_indent = indentation(&self.lines[block.header]).to_string();
+                let attribute_indent = (block.header + 1..block.end)
+                    .find(|index| attribute_line(&self.lines[*index]).is_some())
+                    .map_or(format!("{}  ", header_indent), |index| {
+                        indentation(&self.lines[index]).to_string()
+                    });
+                (header_indent, attribute_indent)
+            }
+            None => ("  ".to_string(), "    ".to_string()),
+        };
+        // Keys separated by a blank line get one too
+        let mut separated = blocks.last().is_some_and(|block| {
+            block.header > 0 && self.lines[block.header - 1].trim().is_empty()
+        });
+        while self.lines.last().is_some_and(|line| line.trim().is_empty()) {
+            self.lines.pop();
+            separated = true;
+        }
+        if separated {
+            self.lines.push(String::new());
+        }
+        self.lines.push(format!("{}[{}]", header_indent, key));
+        for (name, value) in attributes {
+            self.lines.push(format!(
+                "{}{} = {}",
+                attribute_indent,
+                name,
+                write_value(value)
+            ));
+        }
+        self.trailing_newline = true;
+    }
+
+    /// A key may have a single and a plural block, plural attributes go to the plural one
+    fn find_block(&self, key: &str, attribute: &str) -> Option<Block<'_>> {
+        let wants_plural = attribute.contains(':');
+        let mut blocks = self.blocks().into_iter().filter(|block| block.name == key);
+        let first = blocks.next()?;
+        if self.is_plural(&first) == wants_plural {
+            return Some(first);
+        }
+        Some(
+            blocks
+                .find(|block| self.is_plural(block) == wants_plural)
+                .unwrap_or(first),
+        )
+    }
+
+    fn is_plural(&self, block: &Block) -> bool {
+        (block.header + 1..block.end).any(|index| {
+            matches!(attribute_line(&self.lines[index]), Some((name, _)) if name.contains(':'))
+        })
+    }
+
+    fn blocks(&self) -> Vec<Block<'_>> {
+        let headers: Vec<(usize, Option<&str>)> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let line = line.trim();
+                if line.starts_with("[[") {
+                    Some((index, None))
+                } else if line.starts_with('[') && line.ends_with(']') {
+                    Some((index, Some(&line[1..line.len() - 1])))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        headers
+            .iter()
+            .enumerate()
+            .filter_map(|(position, (header, name))| {
+                let end = headers
+                    .get(position + 1)
+                    .map_or(self.lines.len(), |(next, _)| *next);
+                name.map(|name| Block {
+                    name,
+                    header: *header,
+                    end,
+                })
+            })
+            .collect()
+    }
+
+    /// The document in the canonical layout: sections unindented, keys sorted by name and
+    /// separated by blank lines, `comment` and `tags` first, then `default_lang` and the other
+    /// locales alphabetically with plural quantities in CLDR order. Texts are kept as written.
+    ///
+    /// Sections keep their order, keys are sorted within their section and a single key goes
+    /// before a plural one of the same name. `#` comments move with the line they precede,
+    /// comments at the end of the document stay there. Formatting a formatted document
+    /// gives it back unchanged.
+    pub fn format(&self, default_lang: &str) -> String {
+        let mut sections = vec![FormatSection {
+            header: None,
+            loose: Vec::new(),
+            keys: Vec::new(),
+        }];
+        let mut comments: Vec<String> = Vec::new();
+        for line in &self.lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                comments.push(line.to_string());
+                continue;
+            }
+            let comments = std::mem::take(&mut comments);
+            if line.starts_with("[[") {
+                sections.push(FormatSection {
+                    header: Some(Commented {
+                        comments,
+                        item: line.to_string(),
+                    }),
+                    loose: Vec::new(),
+                    keys: Vec::new(),
+                });
+            } else if line.starts_with('[') && line.ends_with(']') {
+                sections.last_mut().unwrap().keys.push(Commented {
+                    comments,
+                    item: FormatKey {
+                        name: line[1..line.len() - 1].to_string(),
+                        attributes: Vec::new(),
+                    },
+                });
+            } else {
+                let item = match attribute_line(line) {
+                    Some((name, value)) => format!("{} = {}", name, value),
+                    None => line.to_string(),
+                };
+                let attribute = Commented { comments, item };
+                let section = sections.last_mut().unwrap();
+                match section.keys.last_mut() {
+                    Some(key) => key.item.attributes.push(attribute),
+                    None => section.loose.push(attribute),
+                }
+            }
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+        for mut section in sections {
+            if section.header.is_none() && section.loose.is_empty() && section.keys.is_empty() {
+                continue;
+            }
+            if let Some(header) = &section.header {
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                push_commented(&mut lines, "", header);
+            }
+            for attribute in &section.loose {
+                push_commented(&mut lines, "    ", attribute);
+            }
+            section.keys.sort_by_cached_key(|key| {
+                let is_plural = key
+                    .item
+                    .attributes
+                    .iter()
+                    .any(|attribute| attribute_name(&attribute.item).contains(':'));
+                (key.item.name.clone(), is_plural)
+            });
+            for (index, mut key) in section.keys.into_iter().enumerate() {
+                if index > 0 || (section.header.is_none() && !lines.is_empty()) {
+                    lines.push(String::new());
+                }
+                key.item.attributes.sort_by_cached_key(|attribute| {
+                    attribute_order(attribute_name(&attribute.item), default_lang)
+                });
+                let header = Commented {
+                    comments: key.comments,
+                    item: format!("[{}]", key.item.name),
+                };
+                push_commented(&mut lines, "  ", &header);
+                for attribute in &key.item.attributes {
+                    push_commented(&mut lines, "    ", attribute);
+                }
+            }
+        }
+        lines.extend(comments);
+        let mut result = lines.join("\n");
+        result.push('\n');
+        result
+    }
+}
+
+fn push_commented(lines: &mut Vec<String>, indent: &str, line: &Commented<String>) {
+    lines.extend(
+        line.comments
+            .iter()
+            .map(|comment| format!("{}{}", indent, comment)),
+    );
+    lines.push(format!("{}{}", indent, line.item));
+}
+
+fn attribute_name(line: &str) -> &str {
+    line.split_once('=').map_or(line, |(name, _)| name.trim())
+}
+
+/// Sort key of an attribute: `comment`, `tags`, the default locale, then other locales
+fn attribute_order(name: &str, default_lang: &str) -> (u8, String, usize) {
+    let (locale, quantity) = name.split_once(':').unwrap_or((name, ""));
+    let rank = match locale {
+        "comment" => 0,
+        "tags" => 1,
+        locale if locale == default_lang => 2,
+        _ => 3,
+    };
+    (rank, locale.to_string(), plural::order(quantity))
+}
+
+impl fmt::Display for TwineDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lines.join("\n"))?;
+        if self.trailing_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders `file` as a new twine file, every section under its `[[name]]` header with
+/// localizations in the order of the model. Parsed texts are unescaped, so parsing the
+/// rendered file gives the same texts back.
+pub fn render(file: &File) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for section in &file.sections {
+        if let Some(name) = &section.name {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("[[{}]]", name));
+        }
+        for (index, key) in section.keys.iter().enumerate() {
+            if index > 0 || (section.name.is_none() && !lines.is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("  [{}]", key.name));
+            for (name, value) in key_attributes(key) {
+                lines.push(format!("    {} = {}", name, write_value(&value)));
+            }
+        }
+    }
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// Comment and tags of `key` followed by its localizations, as `name = value` pairs.
+/// Texts are unescaped into the form they are written in twine files.
+pub fn key_attributes(key: &Key) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    if let Some(comment) = &key.comment {
+        attributes.push(("comment".to_string(), comment.clone()));
+    }
+    if !key.tags.is_empty() {
+        attributes.push(("tags".to_string(), key.tags.join(",")));
+    }
+    for str in &key.localizations {
+        match &str.value {
+            StringValue::Single(text) => {
+                attributes.push((str.language_code.clone(), parse::unescape(text)))
+            }
+            StringValue::Plural { quantities } => {
+                attributes.extend(quantities.iter().map(|plural| {
+                    (
+                        format!("{}:{}", str.language_code, plural.quantity),
+                        parse::unescape(&plural.text),
+                    )
+                }))
+            }
+        }
+    }
+    attributes
+}
+
+/// `value` as written in a twine file: newlines are escaped and values with leading or
+/// trailing whitespace are wrapped in backticks, which twine strips when reading
+fn write_value(value: &str) -> String {
+    let value = value.replace('\n', "\\n");
+    let padded = value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace);
+    let backticked = value.len() > 1 && value.starts_with('`') && value.ends_with('`');
+    match padded || backticked {
+        true => format!("`{}`", value),
+        false => value,
+    }
+}
+
+/// Name and value of an `name = value` line, comments and blank lines are `None`
+fn attribute_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with('#') || line.starts_with('[') {
+        return None;
+    }
+    let (name, value) = line.split_once('=')?;
+    Some((name.trim(), value.trim()))
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{LocalizedString, Section};
+
+#[cfg(test)]
+impl TwineDocument {
+    fn contains_key(&self, key: &str) -> bool {
+        self.blocks().iter().any(|block| block.name == key)
+    }
+
+    /// Value of `attribute` of `key`, e.g. "en", "ru:few" or "comment"
+    fn get(&self, key: &str, attribute: &str) -> Option<&str> {
+        let block = self.find_block(key, attribute)?;
+        (block.header + 1..block.end).find_map(|index| match attribute_line(&self.lines[index]) {
+            Some((name, value)) if name == attribute => Some(value),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+fn parse_twine(text: &str) -> Result<File> {
+    let dir = assert_fs::TempDir::new()?;
+    let path = dir.path().join("strings.txt");
+    fs::write(&path, text)?;
+    parse::parse(&path).map_err(|err| anyhow!(err))
+}
+
+#[cfg(test)]
+const SAMPLE: &str = "[[Main]]
+  [find]
+    # Shown in the toolbar
+    en = Find
+    comment = Search button
+
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+  [songs]
+    en = Songs
+";
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn reads_attributes() {
+    let doc = TwineDocument::parse(SAMPLE);
+    assert_eq!(doc.get("find", "en"), Some("Find"));
+    assert_eq!(doc.get("find", "comment"), Some("Search button"));
+    assert_eq!(doc.get("songs", "ru:one"), Some("%d песня"));
+    assert_eq!(doc.get("songs", "en"), Some("Songs"));
+    assert_eq!(doc.get("find", "ru"), None);
+    assert!(!doc.contains_key("missing"));
+}
+
+#[test]
+fn sets_attributes_keeping_layout() {
+    let mut doc = TwineDocument::parse(SAMPLE);
+    assert!(doc.set("find", "en", "Search"));
+    assert!(doc.set("find", "ru", "Найти\nвсё"));
+    assert!(doc.set("songs", "ru:few", "%d песни"));
+    assert!(doc.set("songs", "ru", "Песни"));
+    assert!(!doc.set("missing", "ru", "Нет"));
+    let expected = "[[Main]]
+  [find]
+    # Shown in the toolbar
+    en = Search
+    comment = Search button
+    ru = Найти\\nвсё
+
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+    ru:few = %d песни
+  [songs]
+    en = Songs
+    ru = Песни
+";
+    assert_eq!(doc.to_string(), expected);
+}
+
+#[test]
+fn adds_keys_to_the_end() {
+    let mut doc = TwineDocument::parse(SAMPLE);
+    doc.add_key(
+        "open",
+        &[
+            ("comment".to_string(), "Opens a file".to_string()),
+            ("en".to_string(), "Open".to_string()),
+        ],
+    );
+    let expected = format!(
+        "{}  [open]\n    comment = Opens a file\n    en = Open\n",
+        SAMPLE
+    );
+    assert_eq!(doc.to_string(), expected);
+}
+
+#[test]
+fn formats_documents() {
+    let text = "# Strings of the app
+[[Main]]
+[zoom]
+en=Zoom
+  [songs]
+      ru:one = %d песня
+    # Plural
+      en:other = %d songs
+    en:one = %d song
+
+
+    [find]
+    ru = Найти
+    en = Find
+    comment = Search button
+  [songs]
+    en = Songs
+# The end
+";
+    let expected = "# Strings of the app
+[[Main]]
+  [find]
+    comment = Search button
+    en = Find
+    ru = Найти
+
+  [songs]
+    en = Songs
+
+  [songs]
+    en:one = %d song
+    # Plural
+    en:other = %d songs
+    ru:one = %d песня
+
+  [zoom]
+    en = Zoom
+# The end
+";
+    let formatted = TwineDocument::parse(text).format("en");
+    assert_eq!(formatted, expected);
+    assert_eq!(TwineDocument::parse(&formatted).format("en"), formatted);
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let text = "[[Main]]
+  [songs]
+    ru:many = %d песен
+    en:other = %d songs
+  [songs]
+    # Title of the list
+    en = Songs
+  [about]
+    en = About
+    # Trailing comment of the key
+
+[[Settings]]
+  [title]
+    de = Titel
+    en = Settings
+# Trailing comment of the file
+";
+    let once = TwineDocument::parse(text).format("en");
+    let twice = TwineDocument::parse(&once).format("en");
+    assert_eq!(twice, once);
+    assert!(once.ends_with("# Trailing comment of the file\n"));
+}
+
+#[test]
+fn renders_parsed_files() -> Result<()> {
+    let text = "[[Strings]]
+  [songs]
+    comment = Number of songs
+    tags = player,library
+    en:one = %d song
+    en:other = %d songs
+
+  [songs]
+    en = Songs
+
+[[Settings]]
+  [title]
+    en = Settings
+";
+    let file = parse_twine(text)?;
+    let names: Vec<_> = file.sections.iter().map(|s| s.name.as_deref()).collect();
+    assert_eq!(names, vec![Some("Strings"), Some("Settings")]);
+    assert_eq!(render(&file), text);
+    Ok(())
+}
+
+#[test]
+fn renders_texts_unescaped_quoting_padded_ones() -> Result<()> {
+    let key = |name: &str, text: &str| Key {
+        name: name.to_string(),
+        comment: None,
+        tags: Vec::new(),
+        localizations: vec![LocalizedString {
+            language_code: "en".to_string(),
+            value: StringValue::Single(parse::parse_text(text).unwrap()),
+        }],
+    };
+    let file = File {
+        sections: vec![Section {
+            name: Some("Strings".to_string()),
+            keys: vec![
+                key("share", "Don't share \"%@\" & <b>%s</b>, 50% off"),
+                key("indent", "  padded "),
+            ],
+        }],
+    };
+    let text = render(&file);
+    let expected = "[[Strings]]
+  [share]
+    en = Don't share \"%1$s\" & <b>%2$s</b>, 50%% off
+
+  [indent]
+    en = `  padded `
+";
+    assert_eq!(text, expected);
+    assert_eq!(
+        parse_twine(&text)?.sections[0].keys[1].localizations[0].value,
+        StringValue::Single("  padded ".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn parsing_rendered_files_gives_the_same_texts() -> Result<()> {
+    let texts = [
+        "Tom & Jerry",
+        "&amp; is an entity",
+        "Don't \"quote\" me",
+        "<b>Bold</b> & <i>italic</i> <not a tag>",
+        "100% of %d, 50%% off",
+        "%@ and %s, %1$d of %2$d",
+        "Line\\nbreak",
+        "  padded ",
+        "`backticks`",
+    ];
+    let mut text = String::from("[[Strings]]\n");
+    for (index, value) in texts.iter().map(|text| write_value(text)).enumerate() {
+        text.push_str(&format!("  [key{index}]\n    en = {value}\n"));
+        text.push_str(&format!("  [plural{index}]\n    en:other = {value}\n"));
+    }
+    let parsed = parse_twine(&text)?;
+    let rendered = render(&parsed);
+    let reparsed = parse_twine(&rendered)?;
+    let values = |file: &File| -> Vec<StringValue> {
+        file.sections
+            .iter()
+            .flat_map(|section| &section.keys)
+            .map(|key| key.localizations[0].value.clone())
+            .collect()
+    };
+    assert_eq!(values(&reparsed), values(&parsed));
+    assert_eq!(render(&reparsed), rendered);
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Ok, Result};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::generator::{GenResult, Generator, StrLines};
+use crate::output::OutputFiles;
+use crate::parse::{self, Placeholder, PluralValue, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Name of the argument with the plural quantity
+pub(crate) const COUNT: &str = "count";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum KeyMode {
+    /// Key names are split on "." into nested objects
+    #[default]
+    Nested,
+    /// Key names are used as they are
+    Flat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum PluralStyle {
+    /// A key per quantity with i18next suffixes, e.g. "songs_one"
+    #[default]
+    I18next,
+    /// ICU message syntax, e.g. "{count, plural, one {# song} other {# songs}}"
+    Icu,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum PlaceholderStyle {
+    /// "%1$s" becomes "{{arg1}}"
+    #[default]
+    Named,
+    /// "%1$s" becomes "{0}"
+    Indexed,
+}
+
+/// Writes `<locale>.json` with keys of all sources for every locale
+#[derive(Default)]
+pub struct WebGenerator {
+    pub keys: KeyMode,
+    pub plurals: PluralStyle,
+    pub placeholders: PlaceholderStyle,
+}
+
+impl Generator for WebGenerator {
+    fn name(&self) -> &str {
+        "web"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let generated = GenResult::from_files(sources.iter().map(|src| &src.file))?;
+        self.render_files(&generated, &options.output_dir, &options.default_lang)
+    }
+}
+
+impl WebGenerator {
+    pub fn render_files(
+        &self,
+        generated: &GenResult,
+        dir: &Path,
+        default_lang: &Option<String>,
+    ) -> Result<OutputFiles> {
+        let defaults = default_plurals(generated, default_lang);
+        generated
+            .value
+            .par_iter()
+            .map(|(locale, lines)| {
+                let contents = self
+                    .render_locale(lines, &defaults)
+                    .map_err(|err| anyhow!("Locale \"{}\": {}", locale.value, err))?;
+                Ok((dir.join(format!("{}.json", locale.value)), contents))
+            })
+            .collect()
+    }
+
+    fn render_locale(&self, lines: &StrLines, defaults: &DefaultPlurals) -> Result<String> {
+        let mut root = Node::Object(Vec::new());
+        for line in &lines.value {
+            match line.value {
+                StringValue::Single(text) => {
+                    self.insert(&mut root, line.name, self.convert_text(text, false, None))?
+                }
+                StringValue::Plural { quantities } => match self.plurals {
+                    PluralStyle::I18next => {
+                        let count_position = count_position(quantities);
+                        for plural in quantities {
+                            let name = format!("{}_{}", line.name, plural.quantity);
+                            let text = self.convert_text(&plural.text, true, count_position);
+                            self.insert(&mut root, &name, text)?;
+                        }
+                    }
+                    PluralStyle::Icu => {
+                        let quantities =
+                            icu_quantities(quantities, defaults.get(line.name).copied());
+                        self.insert(&mut root, line.name, self.convert_icu_plural(&quantities))?
+                    }
+                },
+            }
+        }
+        let mut result = serde_json::to_string_pretty(&root)?;
+        result.push('\n');
+        Ok(result)
+    }
+
+    fn insert(&self, root: &mut Node, name: &str, text: String) -> Result<()> {
+        let inserted = match self.keys {
+            KeyMode::Flat => root.insert(&[name], text),
+            KeyMode::Nested => root.insert(&name.split('.').collect::<Vec<_>>(), text),
+        };
+        match inserted {
+            true => Ok(()),
+            false => Err(anyhow!("Key \"{}\" collides with another key", name)),
+        }
+    }
+
+    /// Converts placeholders of a single text or a plural branch,
+    /// the placeholder at `count_position` is the plural quantity
+    fn convert_text(&self, text: &str, in_plural: bool, count_position: Option<usize>) -> String {
+        let text = parse::unescape(text);
+        let text = match self.plurals {
+            PluralStyle::Icu => escape_icu(&text, in_plural),
+            PluralStyle::I18next => text,
+        };
+        parse::replace_placeholders(&text, "%", |placeholder| {
+            let is_count = count_position == Some(placeholder.position);
+            match (self.plurals, is_count) {
+                // ICU plural branches refer to the quantity with "#"
+                (PluralStyle::Icu, true) => "#".to_string(),
+                // i18next always passes the quantity as "count"
+                (PluralStyle::I18next, true) => format!("{{{{{}}}}}", COUNT),
+                _ => self.placeholder(placeholder),
+            }
+        })
+    }
+
+    fn placeholder(&self, placeholder: &Placeholder) -> String {
+        match self.placeholders {
+            PlaceholderStyle::Named => format!("{{{{arg{}}}}}", placeholder.position),
+            PlaceholderStyle::Indexed => format!("{{{}}}", placeholder.position - 1),
+        }
+    }
+
+    fn convert_icu_plural(&self, quantities: &[PluralValue]) -> String {
+        let count_position = count_position(quantities);
+        let selector = match (self.placeholders, count_position) {
+            (PlaceholderStyle::Indexed, Some(position)) => (position - 1).to_string(),
+            _ => COUNT.to_string(),
+        };
+        let branches: Vec<String> = quantities
+            .iter()
+            .map(|plural| {
+                format!(
+                    "{} {{{}}}",
+                    plural.quantity,
+                    self.convert_text(&plural.text, true, count_position)
+                )
+            })
+            .collect();
+        format!("{{{}, plural, {}}}", selector, branches.join(" "))
+    }
+}
+
+/// Position of the first integer placeholder, which takes the plural quantity
+pub(crate) fn count_position(quantities: &[PluralValue]) -> Option<usize> {
+    quantities
+        .iter()
+        .flat_map(|plural| parse::placeholders(&plural.text))
+        .find(Placeholder::is_integer)
+        .map(|placeholder| placeholder.position)
+}
+
+/// Plurals of the default language by key name
+pub(crate) type DefaultPlurals<'a> = HashMap<&'a str, &'a [PluralValue]>;
+
+pub(crate) fn default_plurals<'a>(
+    generated: &'a GenResult,
+    default_lang: &Option<String>,
+) -> DefaultPlurals<'a> {
+    let lines = generated
+        .value
+        .iter()
+        .find(|(locale, _)| Some(&locale.value) == default_lang.as_ref())
+        .map(|(_, lines)| lines.value.as_slice())
+        .unwrap_or_default();
+    lines
+        .iter()
+        .filter_map(|line| match line.value {
+            StringValue::Plural { quantities } => Some((line.name, quantities.as_slice())),
+            StringValue::Single(_) => None,
+        })
+        .collect()
+}
+
+/// Quantities of an ICU plural, which must have an "other" branch. A plural lacking it
+/// takes the "other" text of the `default` language plural, or else its last quantity.
+pub(crate) fn icu_quantities(
+    quantities: &[PluralValue],
+    default: Option<&[PluralValue]>,
+) -> Vec<PluralValue> {
+    let mut result = quantities.to_vec();
+    if quantities.iter().any(|plural| plural.quantity == "other") {
+        return result;
+    }
+    let other = default
+        .and_then(|default| default.iter().find(|plural| plural.quantity == "other"))
+        .or(quantities.last());
+    if let Some(other) = other {
+        result.push(PluralValue {
+            quantity: "other".to_string(),
+            text: other.text.clone(),
+        });
+    }
+    result
+}
+
+/// Quotes ICU syntax characters of a plain text, "#" is only special in plural branches
+pub(crate) fn escape_icu(text: &str, in_plural: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '{' | '}' => result.push_str(&format!("'{}'", c)),
+            '\'' => result.push_str("''"),
+            '#' if in_plural => result.push_str("'#'"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// JSON value keeping the order of keys as they are in the sources
+enum Node {
+    Text(String),
+    Object(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Inserts `text` at `path`, returns false if a part of the path is already a text
+    fn insert(&mut self, path: &[&str], text: String) -> bool {
+        let (Node::Object(entries), Some((first, rest))) = (self, path.split_first()) else {
+            return false;
+        };
+        let index = match entries.iter().position(|(key, _)| key == first) {
+            Some(index) => index,
+            None => {
+                let node = match rest.is_empty() {
+                    true => Node::Text(String::new()),
+                    false => Node::Object(Vec::new()),
+                };
+                entries.push((first.to_string(), node));
+                entries.len() - 1
+            }
+        };
+        match (&mut entries[index].1, rest.is_empty()) {
+            (Node::Text(value), true) => {
+                *value = text;
+                true
+            }
+            (node @ Node::Object(_), false) => node.insert(rest, text),
+            _ => false,
+        }
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Node::Text(text) => serializer.serialize_str(text),
+            Node::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::generator::Line;
+
+#[cfg(test)]
+fn lines(values: &'static [(&'static str, StringValue)]) -> StrLines<'static> {
+    StrLines {
+        value: values
+            .iter()
+            .map(|(name, value)| Line {
+                name,
+                comment: None,
+                tags: &[],
+                value,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+fn sample() -> &'static [(&'static str, StringValue)] {
+    let plural = |quantity: &str, text: &str| PluralValue {
+        quantity: quantity.to_string(),
+        text: text.to_string(),
+    };
+    Box::leak(Box::new([
+        (
+            "login.title",
+            StringValue::Single("Hi %1$s, {you} have %2$d%%".to_string()),
+        ),
+        (
+            "login.songs",
+            StringValue::Plural {
+                quantities: vec![
+                    plural("one", "%1$d song by %2$s"),
+                    plural("other", "%1$d songs by %2$s"),
+                ],
+            },
+        ),
+    ]))
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn renders_nested_keys_with_i18next_plurals() -> Result<()> {
+    let generator = WebGenerator::default();
+    let expected = r#"{
+  "login": {
+    "title": "Hi {{arg1}}, {you} have {{arg2}}%",
+    "songs_one": "{{count}} song by {{arg2}}",
+    "songs_other": "{{count}} songs by {{arg2}}"
+  }
+}
+"#;
+    assert_eq!(
+        generator.render_locale(&lines(sample()), &HashMap::new())?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn renders_flat_keys_with_icu_plurals() -> Result<()> {
+    let generator = WebGenerator {
+        keys: KeyMode::Flat,
+        plurals: PluralStyle::Icu,
+        placeholders: PlaceholderStyle::Indexed,
+    };
+    let expected = r#"{
+  "login.title": "Hi {0}, '{'you'}' have {1}%",
+  "login.songs": "{0, plural, one {# song by {1}} other {# songs by {1}}}"
+}
+"#;
+    assert_eq!(
+        generator.render_locale(&lines(sample()), &HashMap::new())?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn fails_on_colliding_nested_keys() {
+    let values: &'static [(&'static str, StringValue)] = Box::leak(Box::new([
+        ("login", StringValue::Single("Login".to_string())),
+        ("login.title", StringValue::Single("Title".to_string())),
+    ]));
+    let generator = WebGenerator::default();
+    assert!(generator
+        .render_locale(&lines(values), &HashMap::new())
+        .is_err());
+}
+
+#[test]
+fn completes_icu_plurals_with_other() {
+    let plural = |quantity: &str, text: &str| PluralValue {
+        quantity: quantity.to_string(),
+        text: text.to_string(),
+    };
+    let ru = [plural("one", "%d песня"), plural("few", "%d песни")];
+    let en = [plural("one", "%d song"), plural("other", "%d songs")];
+    assert_eq!(
+        icu_quantities(&ru, Some(&en)),
+        vec![ru[0].clone(), ru[1].clone(), plural("other", "%d songs")]
+    );
+    assert_eq!(
+        icu_quantities(&ru, None),
+        vec![ru[0].clone(), ru[1].clone(), plural("other", "%d песни")]
+    );
+    assert_eq!(icu_quantities(&en, None), en.to_vec());
+}
+
+#[test]
+fn escapes_icu_syntax() {
+    assert_eq!(escape_icu("{it's} #1", false), "'{'it''s'}' #1");
+    assert_eq!(escape_icu("#1", true), "'#'1");
+}
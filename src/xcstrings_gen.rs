@@ -0,0 +1,324 @@
+use anyhow::{anyhow, Ok, Result};
+use serde_json::ser::{Formatter, PrettyFormatter};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io;
+
+use crate::generator::Generator;
+use crate::ios_gen::table_name;
+use crate::output::OutputFiles;
+use crate::parse::{self, StringValue};
+use crate::pipeline::{GenOptions, Source};
+
+/// Writes `<file name>.xcstrings`, a String Catalog with keys of all sources in every locale
+pub struct XcstringsGenerator;
+
+impl Generator for XcstringsGenerator {
+    fn name(&self) -> &str {
+        "xcstrings"
+    }
+
+    fn render(&self, sources: &[Source], options: &GenOptions) -> Result<OutputFiles> {
+        let source_language = options
+            .default_lang
+            .as_deref()
+            .ok_or_else(|| anyhow!("xcstrings needs the default language to set sourceLanguage"))?;
+        let path = options
+            .output_dir
+            .join(format!("{}.xcstrings", table_name(options)));
+        Ok(OutputFiles::from([(
+            path,
+            render(&catalog(sources, source_language))?,
+        )]))
+    }
+}
+
+/// Catalog as JSON, keys of objects are sorted like Xcode does.
+/// Keys of the same name are merged, but a plural one replaces a single one like the
+/// `.stringsdict` does on iOS, a catalog can't hold a string and a plural of one name.
+pub fn catalog(sources: &[Source], source_language: &str) -> Value {
+    let mut strings = Map::new();
+    let mut plurals: HashMap<&str, bool> = HashMap::new();
+    let keys = sources
+        .iter()
+        .flat_map(|source| &source.file.sections)
+        .flat_map(|section| &section.keys);
+    for key in keys {
+        let is_plural = key
+            .localizations
+            .iter()
+            .any(|str| matches!(str.value, StringValue::Plural { .. }));
+        match plurals.get(key.name.as_str()) {
+            Some(true) if !is_plural => continue,
+            Some(false) if is_plural => {
+                strings.remove(&key.name);
+            }
+            _ => (),
+        }
+        plurals.insert(&key.name, is_plural);
+        let entry = strings.entry(key.name.clone()).or_insert_with(|| {
+            json!({
+                "extractionState": "manual",
+                "localizations": {},
+            })
+        });
+        if let (Some(comment), None) = (&key.comment, entry.get("comment")) {
+            entry["comment"] = json!(comment);
+        }
+        for str in &key.localizations {
+            entry["localizations"][&str.language_code] = localization(&str.value);
+        }
+    }
+    json!({
+        "sourceLanguage": source_language,
+        "strings": strings,
+        "version": "1.0",
+    })
+}
+
+fn localization(value: &StringValue) -> Value {
+    match value {
+        StringValue::Single(text) => string_unit(text),
+        StringValue::Plural { quantities } => {
+            let plural: Map<String, Value> = quantities
+                .iter()
+                .map(|plural| (plural.quantity.clone(), string_unit(&plural.text)))
+                .collect();
+            json!({ "variations": { "plural": plural } })
+        }
+    }
+}
+
+fn string_unit(text: &str) -> Value {
+    json!({
+        "stringUnit": {
+            "state": "translated",
+            "value": to_apple_format(&parse::unescape(text)),
+        }
+    })
+}
+
+/// Parsed strings use "%s" for string arguments, Apple formats use "%@"
+fn to_apple_format(text: &str) -> String {
+    parse::replace_placeholders(text, "%%", |placeholder| match placeholder.conversion {
+        's' => format!("{}@", &placeholder.spec[..placeholder.spec.len() - 1]),
+        _ => placeholder.spec.clone(),
+    })
+}
+
+/// Serializes like Xcode, with two spaces indentation and " : " between keys and values,
+/// so catalogs saved by Xcode don't show up as changed
+pub fn render(catalog: &Value) -> Result<String> {
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, XcodeFormatter::new());
+    serde::Serialize::serialize(catalog, &mut serializer)?;
+    out.push(b'\n');
+    Ok(String::from_utf8(out)?)
+}
+
+struct XcodeFormatter {
+    pretty: PrettyFormatter<'static>,
+}
+
+impl XcodeFormatter {
+    fn new() -> XcodeFormatter {
+        XcodeFormatter {
+            pretty: PrettyFormatter::with_indent(b"  "),
+        }
+    }
+}
+
+impl Formatter for XcodeFormatter {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.pretty.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.pretty.begin_object_key(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b" : ")
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object_value(writer)
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+use crate::parse::{File, Key, LocalizedString, PluralValue, Section};
+
+#[cfg(test)]
+fn source(keys: Vec<Key>) -> Source {
+    Source {
+        name: "strings".to_string(),
+        file: File {
+            sections: vec![Section { name: None, keys }],
+        },
+    }
+}
+
+#[cfg(test)]
+fn key(name: &str, comment: Option<&str>, localizations: Vec<LocalizedString>) -> Key {
+    Key {
+        name: name.to_string(),
+        comment: comment.map(str::to_string),
+        tags: Vec::new(),
+        localizations,
+    }
+}
+
+#[cfg(test)]
+fn plain_str(lang: &str, txt: &str) -> LocalizedString {
+    LocalizedString {
+        language_code: lang.to_string(),
+        value: StringValue::Single(txt.to_string()),
+    }
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn builds_catalog_of_all_sources() {
+    let songs = LocalizedString {
+        language_code: "ru".to_string(),
+        value: StringValue::Plural {
+            quantities: vec![
+                PluralValue {
+                    quantity: "one".to_string(),
+                    text: "%d песня".to_string(),
+                },
+                PluralValue {
+                    quantity: "many".to_string(),
+                    text: "%d песен".to_string(),
+                },
+            ],
+        },
+    };
+    let sources = vec![
+        source(vec![key(
+            "find",
+            Some("Search button"),
+            vec![plain_str("en", "Find \\\"%s\\\""), plain_str("ru", "Найти")],
+        )]),
+        source(vec![key("songs", None, vec![songs])]),
+    ];
+    let expected = json!({
+        "sourceLanguage": "en",
+        "strings": {
+            "find": {
+                "comment": "Search button",
+                "extractionState": "manual",
+                "localizations": {
+                    "en": { "stringUnit": { "state": "translated", "value": "Find \"%@\"" } },
+                    "ru": { "stringUnit": { "state": "translated", "value": "Найти" } },
+                },
+            },
+            "songs": {
+                "extractionState": "manual",
+                "localizations": {
+                    "ru": { "variations": { "plural": {
+                        "one": { "stringUnit": { "state": "translated", "value": "%d песня" } },
+                        "many": { "stringUnit": { "state": "translated", "value": "%d песен" } },
+                    } } },
+                },
+            },
+        },
+        "version": "1.0",
+    });
+    assert_eq!(expected, catalog(&sources, "en"));
+}
+
+#[test]
+fn keeps_the_plural_of_a_string_and_a_plural_of_the_same_name() {
+    let songs = LocalizedString {
+        language_code: "en".to_string(),
+        value: StringValue::Plural {
+            quantities: vec![PluralValue {
+                quantity: "other".to_string(),
+                text: "%d songs".to_string(),
+            }],
+        },
+    };
+    let sources = vec![
+        source(vec![key(
+            "songs",
+            Some("Title"),
+            vec![plain_str("en", "Songs")],
+        )]),
+        source(vec![key("songs", None, vec![songs])]),
+        source(vec![key("songs", None, vec![plain_str("ru", "Песни")])]),
+    ];
+    let expected = json!({
+        "sourceLanguage": "en",
+        "strings": {
+            "songs": {
+                "extractionState": "manual",
+                "localizations": {
+                    "en": { "variations": { "plural": {
+                        "other": { "stringUnit": { "state": "translated", "value": "%d songs" } },
+                    } } },
+                },
+            },
+        },
+        "version": "1.0",
+    });
+    assert_eq!(expected, catalog(&sources, "en"));
+}
+
+#[test]
+fn converts_string_placeholders() {
+    assert_eq!(
+        to_apple_format("%1$s and %2$-5s, %%s %d"),
+        "%1$@ and %2$-5@, %%s %d"
+    );
+}
+
+#[test]
+fn renders_like_xcode() -> Result<()> {
+    let rendered = render(&json!({ "sourceLanguage": "en", "strings": { "a": {} } }))?;
+    assert_eq!(
+        rendered,
+        "{\n  \"sourceLanguage\" : \"en\",\n  \"strings\" : {\n    \"a\" : {}\n  }\n}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn requires_default_lang() {
+    let options = GenOptions {
+        output_dir: "out".into(),
+        default_lang: None,
+        file_name: None,
+    };
+    assert!(XcstringsGenerator.render(&[], &options).is_err());
+}
@@ -0,0 +1,365 @@
+//! XLIFF 1.2 and 2.0 files for translators.
+//!
+//! Every source becomes a `<file>`, keys become units with the key name as id. Plurals are
+//! grouped, with a unit per quantity named `<key>:<quantity>`.
+
+use anyhow::{anyhow, Ok, Result};
+use clap::ValueEnum;
+use roxmltree::{Document, Node};
+use std::fs;
+use std::path::Path;
+
+use crate::escape::xml;
+use crate::exchange::{export_entries, target_locales, ExportEntry, SourceText, Translation};
+use crate::output::OutputFiles;
+use crate::pipeline::Source;
+use crate::plural;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum XliffVersion {
+    #[default]
+    #[value(name = "1.2")]
+    V1_2,
+    #[value(name = "2.0")]
+    V2_0,
+}
+
+/// Renders `<locale>.xlf` into `dir` for every target locale, see `target_locales`
+pub fn export(
+    sources: &[Source],
+    default_lang: &str,
+    locales: &[String],
+    version: XliffVersion,
+    dir: &Path,
+) -> Result<OutputFiles> {
+    Ok(target_locales(sources, default_lang, locales)?
+        .into_iter()
+        .map(|locale| {
+            let contents = match version {
+                XliffVersion::V1_2 => render_v1_2(sources, default_lang, &locale),
+                XliffVersion::V2_0 => render_v2_0(sources, default_lang, &locale),
+            };
+            (dir.join(format!("{}.xlf", locale)), contents)
+        })
+        .collect())
+}
+
+fn render_v1_2(sources: &[Source], default_lang: &str, locale: &str) -> String {
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    result.push_str("<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+    for source in sources {
+        result.push_str(&format!(
+            "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\">\n",
+            xml(&source.name),
+            xml(default_lang),
+            xml(locale)
+        ));
+        result.push_str("    <body>\n");
+        for entry in export_entries(&source.file, default_lang, locale) {
+            render_entry_v1_2(&entry, &mut result);
+        }
+        result.push_str("    </body>\n");
+        result.push_str("  </file>\n");
+    }
+    result.push_str("</xliff>\n");
+    result
+}
+
+fn render_entry_v1_2(entry: &ExportEntry, result: &mut String) {
+    let name = &entry.key.name;
+    let plural = entry.texts.iter().any(|text| text.quantity.is_some());
+    let indent = if plural {
+        result.push_str(&format!(
+            "      <group id=\"{}\" restype=\"x-gettext-plurals\">\n",
+            xml(name)
+        ));
+        "        "
+    } else {
+        "      "
+    };
+    for text in &entry.texts {
+        result.push_str(&format!(
+            "{}<trans-unit id=\"{}\">\n",
+            indent,
+            xml(&unit_id(name, &text.quantity))
+        ));
+        result.push_str(&format!(
+            "{}  <source>{}</source>\n",
+            indent,
+            xml(&text.source)
+        ));
+        if let Some(target) = &text.target {
+            result.push_str(&format!(
+                "{}  <target state=\"translated\">{}</target>\n",
+                indent,
+                xml(target)
+            ));
+        }
+        if let (Some(comment), false) = (&entry.key.comment, plural) {
+            result.push_str(&format!("{}  <note>{}</note>\n", indent, xml(comment)));
+        }
+        result.push_str(&format!("{}</trans-unit>\n", indent));
+    }
+    if plural {
+        if let Some(comment) = &entry.key.comment {
+            result.push_str(&format!("        <note>{}</note>\n", xml(comment)));
+        }
+        result.push_str("      </group>\n");
+    }
+}
+
+fn render_v2_0(sources: &[Source], default_lang: &str, locale: &str) -> String {
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    result.push_str(&format!(
+        "<xliff version=\"2.0\" xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" srcLang=\"{}\" trgLang=\"{}\">\n",
+        xml(default_lang),
+        xml(locale)
+    ));
+    for source in sources {
+        result.push_str(&format!("  <file id=\"{}\">\n", xml(&source.name)));
+        for entry in export_entries(&source.file, default_lang, locale) {
+            render_entry_v2_0(&entry, &mut result);
+        }
+        result.push_str("  </file>\n");
+    }
+    result.push_str("</xliff>\n");
+    result
+}
+
+fn render_entry_v2_0(entry: &ExportEntry, result: &mut String) {
+    let name = &entry.key.name;
+    let plural = entry.texts.iter().any(|text| text.quantity.is_some());
+    let notes = |indent: &str| match &entry.key.comment {
+        Some(comment) => format!(
+            "{0}<notes>\n{0}  <note>{1}</note>\n{0}</notes>\n",
+            indent,
+            xml(comment)
+        ),
+        None => String::new(),
+    };
+    let indent = if plural {
+        result.push_str(&format!("    <group id=\"{}\">\n", xml(name)));
+        result.push_str(&notes("      "));
+        "      "
+    } else {
+        "    "
+    };
+    for text in &entry.texts {
+        result.push_str(&format!(
+            "{}<unit id=\"{}\">\n",
+            indent,
+            xml(&unit_id(name, &text.quantity))
+        ));
+        if !plural {
+            result.push_str(&notes(&format!("{}  ", indent)));
+        }
+        let state = match text.target {
+            Some(_) => "translated",
+            None => "initial",
+        };
+        result.push_str(&format!("{}  <segment state=\"{}\">\n", indent, state));
+        result.push_str(&format!(
+            "{}    <source>{}</source>\n",
+            indent,
+            xml(&text.source)
+        ));
+        if let Some(target) = &text.target {
+            result.push_str(&format!("{}    <target>{}</target>\n", indent, xml(target)));
+        }
+        result.push_str(&format!("{}  </segment>\n", indent));
+        result.push_str(&format!("{}</unit>\n", indent));
+    }
+    if plural {
+        result.push_str("    </group>\n");
+    }
+}
+
+fn unit_id(name: &str, quantity: &Option<String>) -> String {
+    match quantity {
+        Some(quantity) => format!("{}:{}", name, quantity),
+        None => name.to_string(),
+    }
+}
+
+/// Reads translated units of an XLIFF 1.2 or 2.0 file, units without a target are skipped
+pub fn read(path: &Path) -> Result<Vec<Translation>> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Cannot read {}: {}", path.display(), err))?;
+    let file_name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+    parse(&text, &file_name).map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))
+}
+
+fn parse(text: &str, file_name: &str) -> Result<Vec<Translation>> {
+    let document = Document::parse(text)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "xliff" {
+        return Err(anyhow!("not an XLIFF file"));
+    }
+    let v2 = root
+        .attribute("version")
+        .is_some_and(|v| v.starts_with('2'));
+    let mut result = Vec::new();
+    for file in root
+        .children()
+        .filter(|node| node.has_tag_name_local("file"))
+    {
+        let (source_lang, target_lang) = match v2 {
+            true => (root.attribute("srcLang"), root.attribute("trgLang")),
+            false => (
+                file.attribute("source-language"),
+                file.attribute("target-language"),
+            ),
+        };
+        let source_lang = source_lang.ok_or_else(|| anyhow!("no source language"))?;
+        let target_lang = target_lang.ok_or_else(|| anyhow!("no target language"))?;
+        let unit_tag = if v2 { "unit" } else { "trans-unit" };
+        for unit in file
+            .descendants()
+            .filter(|node| node.has_tag_name_local(unit_tag))
+        {
+            let id = unit
+                .attribute("id")
+                .ok_or_else(|| anyhow!("unit without id"))?;
+            let source = child_text(unit, "source");
+            let Some(target) = child_text(unit, "target").filter(|t| !t.is_empty()) else {
+                continue;
+            };
+            let (key, quantity) = split_unit_id(id);
+            result.push(Translation {
+                key: key.to_string(),
+                quantity: quantity.map(str::to_string),
+                locale: target_lang.to_string(),
+                source: source.map(|text| SourceText {
+                    locale: source_lang.to_string(),
+                    text,
+                }),
+                text: target,
+                origin: format!("{}: {}", file_name, id),
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Text of the `tag` descendants of `unit`, segments of XLIFF 2.0 units are joined
+fn child_text(unit: Node, tag: &str) -> Option<String> {
+    let texts: Vec<String> = unit
+        .descendants()
+        .filter(|node| node.has_tag_name_local(tag))
+        .map(|node| {
+            node.descendants()
+                .filter(|node| node.is_text())
+                .filter_map(|node| node.text())
+                .collect()
+        })
+        .collect();
+    match texts.is_empty() {
+        true => None,
+        false => Some(texts.concat()),
+    }
+}
+
+fn split_unit_id(id: &str) -> (&str, Option<&str>) {
+    match id.rsplit_once(':') {
+        Some((key, quantity)) if plural::CATEGORIES.contains(&quantity) => (key, Some(quantity)),
+        _ => (id, None),
+    }
+}
+
+trait HasLocalName {
+    fn has_tag_name_local(&self, name: &str) -> bool;
+}
+
+impl HasLocalName for Node<'_, '_> {
+    /// Compares the tag name ignoring its namespace
+    fn has_tag_name_local(&self, name: &str) -> bool {
+        self.is_element() && self.tag_name().name() == name
+    }
+}
+
+// -----------------------------  test tools ------------------------------
+#[cfg(test)]
+fn sources() -> Vec<Source> {
+    vec![crate::exchange::source(
+        "[[Main]]
+  [find]
+    comment = Search & find
+    en = Find \"%s\"
+  [songs]
+    en:one = %d song
+    en:other = %d songs
+    ru:one = %d песня
+",
+    )]
+}
+
+// ------------------------------- tests -----------------------------------
+#[test]
+fn exports_xliff_1_2() -> Result<()> {
+    let files = export(&sources(), "en", &[], XliffVersion::V1_2, Path::new("out"))?;
+    let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file original="strings" source-language="en" target-language="ru" datatype="plaintext">
+    <body>
+      <trans-unit id="find">
+        <source>Find &quot;%s&quot;</source>
+        <note>Search &amp; find</note>
+      </trans-unit>
+      <group id="songs" restype="x-gettext-plurals">
+        <trans-unit id="songs:one">
+          <source>%d song</source>
+          <target state="translated">%d песня</target>
+        </trans-unit>
+        <trans-unit id="songs:few">
+          <source>%d songs</source>
+        </trans-unit>
+        <trans-unit id="songs:many">
+          <source>%d songs</source>
+        </trans-unit>
+        <trans-unit id="songs:other">
+          <source>%d songs</source>
+        </trans-unit>
+      </group>
+    </body>
+  </file>
+</xliff>
+"#;
+    assert_eq!(
+        files.keys().collect::<Vec<_>>(),
+        vec![Path::new("out/ru.xlf")]
+    );
+    assert_eq!(files[Path::new("out/ru.xlf")], expected);
+    Ok(())
+}
+
+#[test]
+fn reads_back_exported_files_of_both_versions() -> Result<()> {
+    for version in [XliffVersion::V1_2, XliffVersion::V2_0] {
+        let files = export(&sources(), "en", &[], version, Path::new("out"))?;
+        let translations = parse(&files[Path::new("out/ru.xlf")], "ru.xlf")?;
+        assert_eq!(
+            translations,
+            vec![Translation {
+                key: "songs".to_string(),
+                quantity: Some("one".to_string()),
+                locale: "ru".to_string(),
+                source: Some(SourceText {
+                    locale: "en".to_string(),
+                    text: "%d song".to_string(),
+                }),
+                text: "%d песня".to_string(),
+                origin: "ru.xlf: songs:one".to_string(),
+            }]
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn fails_without_default_lang_strings() {
+    assert!(export(&sources(), "de", &[], XliffVersion::V1_2, Path::new("out")).is_err());
+}
@@ -0,0 +1,21 @@
+// Generated by utas, do not edit
+package com.example.app
+
+import android.content.Context
+import com.example.app.R
+
+object MainScreen {
+    /** Shown on the start screen */
+    fun greeting(context: Context, arg1: String): String =
+        context.getString(R.string.greeting, arg1)
+
+    fun balance(context: Context, arg1: String, arg2: Double, arg3: Int): String =
+        context.getString(R.string.balance, arg1, arg2, arg3)
+
+    /** Number of songs in a playlist */
+    fun songs(context: Context, quantity: Int, arg1: Int): String =
+        context.resources.getQuantityString(R.plurals.songs, quantity, arg1)
+
+    fun settingsTitle(context: Context): String =
+        context.getString(R.string.settings_title)
+}
@@ -0,0 +1,38 @@
+// Generated by utas, do not edit
+
+import Foundation
+
+enum L10n {
+    /// Shown on the start screen
+    static func greeting(_ p1: String) -> String {
+        L10n.tr("greeting", p1)
+    }
+
+    /// Number of songs in a playlist
+    static func songs(_ count: Int, _ p2: String) -> String {
+        L10n.tr("songs", count, p2)
+    }
+
+    static var `default`: String {
+        L10n.tr("default")
+    }
+
+    enum Settings {
+        static var title: String {
+            L10n.tr("settings.title")
+        }
+
+        enum Balance {
+            static func left(_ p1: String, _ p2: Double, _ p3: Int) -> String {
+                L10n.tr("settings.balance_left", p1, p2, p3)
+            }
+        }
+    }
+}
+
+extension L10n {
+    fileprivate static func tr(_ key: String, _ args: CVarArg...) -> String {
+        let format = NSLocalizedString(key, tableName: "Strings", bundle: .main, comment: "")
+        return String(format: format, locale: Locale.current, arguments: args)
+    }
+}
@@ -0,0 +1,28 @@
+// Generated by utas, do not edit
+
+export type TranslationKey =
+  | "app.title"
+  | "app.say \"hi\""
+  | "player.position"
+  | "player.pointer"
+  | "songs"
+  | "albums"
+  | "download.progress"
+  | "ru.only";
+
+export interface TranslationParams {
+  "app.title": undefined;
+  "app.say \"hi\"": { arg1: string };
+  "player.position": { arg1: number; arg2: number; arg3: string; arg4: string };
+  "player.pointer": { arg1: unknown };
+  "songs": { count: number; arg2: string };
+  "albums": { count: number };
+  "download.progress": { count: number; arg1: string };
+  "ru.only": undefined;
+}
+
+/** Arguments of `t()` for a key, the parameters are left out if it takes none */
+export type TranslationArgs<K extends TranslationKey> =
+  TranslationParams[K] extends undefined
+    ? [key: K]
+    : [key: K, params: TranslationParams[K]];
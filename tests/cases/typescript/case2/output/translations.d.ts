@@ -0,0 +1,32 @@
+// Generated by utas, do not edit
+
+export type TranslationKey =
+  | "app.title"
+  | "app.say \"hi\""
+  | "player.position"
+  | "player.pointer"
+  | "songs"
+  | "albums"
+  | "download.progress"
+  | "ru.only"
+  | "player.skipped"
+  | "playlists";
+
+export interface TranslationParams {
+  "app.title": undefined;
+  "app.say \"hi\"": [string];
+  "player.position": [number, number, string, string];
+  "player.pointer": [unknown];
+  "songs": [number, string];
+  "albums": [number];
+  "download.progress": [string, number];
+  "ru.only": undefined;
+  "player.skipped": [unknown, string];
+  "playlists": { count: number };
+}
+
+/** Arguments of `t()` for a key, the parameters are left out if it takes none */
+export type TranslationArgs<K extends TranslationKey> =
+  TranslationParams[K] extends undefined
+    ? [key: K]
+    : [key: K, params: TranslationParams[K]];
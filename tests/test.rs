@@ -1,8 +1,9 @@
 use assert_cmd::Command;
-use assert_fs::{self};
+use assert_fs::{self, prelude::*};
 use file::{CompareDirsContentResult, Diff, DirDiff};
-use std::fs::create_dir;
+use predicates::prelude::*;
 use std::{error::Error, path::Path};
+use utas::{ErrorMode, GenOptions, InputFilter, Registry, StringValue};
 
 #[test]
 fn case_android_1() -> Result<(), Box<dyn Error>> {
@@ -74,10 +75,540 @@ fn case_android_14() -> Result<(), Box<dyn Error>> {
     basic_test_case("android", "case14", None)
 }
 
+#[test]
+fn case_multiplatform_1() -> Result<(), Box<dyn Error>> {
+    test_case("android,ios", "multiplatform", "case1", None, &[])
+}
+
+#[test]
+fn case_android_15() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "android",
+        "android",
+        "case15",
+        None,
+        &["--recursive", "--include", "**/*.twine", "--exclude", "legacy/**"],
+    )
+}
+
+#[test]
+fn case_android_16() -> Result<(), Box<dyn Error>> {
+    basic_test_case("android", "case16", None)
+}
+
+#[test]
+fn case_plugin_1() -> Result<(), Box<dyn Error>> {
+    let plugin = escargot::CargoBuild::new()
+        .example("props_plugin")
+        .current_release()
+        .run()?;
+    test_case(
+        "props",
+        "plugin",
+        "case1",
+        Some("en".to_string()),
+        &["--plugin", &format!("props={}", plugin.path().display())],
+    )
+}
+
+#[test]
+fn case_template_1() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "template",
+        "template",
+        "case1",
+        Some("en".to_string()),
+        &[
+            "--template",
+            "tests/cases/template/case1/strings.json.jinja",
+            "--template-output",
+            "{locale}/strings.json",
+        ],
+    )
+}
+
+#[test]
+fn case_template_2() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "template",
+        "template",
+        "case2",
+        Some("en".to_string()),
+        &[
+            "--template",
+            "tests/cases/template/case2/strings.xml",
+            "--template-output",
+            "{locale}/strings.xml",
+        ],
+    )
+}
+
+#[test]
+fn case_xcstrings_1() -> Result<(), Box<dyn Error>> {
+    test_case("xcstrings", "xcstrings", "case1", Some("en".to_string()), &[])
+}
+
+#[test]
+fn case_compose_1() -> Result<(), Box<dyn Error>> {
+    test_case("compose", "compose", "case1", Some("en".to_string()), &[])
+}
+
+#[test]
+fn case_arb_1() -> Result<(), Box<dyn Error>> {
+    test_case("arb", "arb", "case1", None, &[])
+}
+
+#[test]
+fn case_kotlin_1() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "kotlin",
+        "kotlin",
+        "case1",
+        Some("en".to_string()),
+        &[
+            "--kotlin-package",
+            "com.example.app",
+            "--kotlin-output-dir",
+            "kotlin",
+        ],
+    )
+}
+
+#[test]
+fn case_kotlin_2() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "android,kotlin",
+        "kotlin",
+        "case2",
+        Some("en".to_string()),
+        &[
+            "--kotlin-package",
+            "com.example.app",
+            "--kotlin-output-dir",
+            "java/com/example/app",
+        ],
+    )
+}
+
+#[test]
+fn case_pseudo_1() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "android",
+        "pseudo",
+        "case1",
+        Some("en".to_string()),
+        &["--pseudo", "en-XA,ar-XB"],
+    )
+}
+
+#[test]
+fn case_swift_1() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "swift",
+        "swift",
+        "case1",
+        Some("en".to_string()),
+        &["Strings"],
+    )
+}
+
+#[test]
+fn case_web_1() -> Result<(), Box<dyn Error>> {
+    test_case("web", "web", "case1", None, &[])
+}
+
+#[test]
+fn case_web_2() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "web",
+        "web",
+        "case2",
+        None,
+        &[
+            "--web-keys",
+            "flat",
+            "--web-plurals",
+            "icu",
+            "--web-placeholders",
+            "indexed",
+        ],
+    )
+}
+
+#[test]
+fn case_typescript_1() -> Result<(), Box<dyn Error>> {
+    test_case("typescript", "typescript", "case1", Some("en".to_string()), &[])
+}
+
+#[test]
+fn case_typescript_2() -> Result<(), Box<dyn Error>> {
+    test_case(
+        "typescript",
+        "typescript",
+        "case2",
+        Some("en".to_string()),
+        &["--web-plurals", "icu", "--web-placeholders", "indexed"],
+    )
+}
+
+#[test]
+fn case_xliff_1() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("xliff").join("case1");
+    Command::cargo_bin("utas")?
+        .args(["export", "xliff"])
+        .arg(case.join("input").as_os_str())
+        .arg(output.path().as_os_str())
+        .args(["en", "--locale", "ru", "--locale", "de"])
+        .assert()
+        .success();
+    assert!(file::dirs_contents_are_same(case.join("output"), output.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_xliff_2() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("xliff").join("case2");
+    std::fs::copy(
+        case.join("input").join("strings.txt"),
+        input.path().join("strings.txt"),
+    )?;
+    Command::cargo_bin("utas")?
+        .args(["import", "xliff"])
+        .arg(input.path().as_os_str())
+        .arg(case.join("ru.xlf").as_os_str())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 translations updated, 1 unchanged, 2 conflicts"))
+        .stdout(predicate::str::contains("ru.xlf: missing: no key \"missing\""));
+    assert!(file::dirs_contents_are_same(case.join("output"), input.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_po_1() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("po").join("case1");
+    Command::cargo_bin("utas")?
+        .args(["export", "po"])
+        .arg(case.join("input").as_os_str())
+        .arg(output.path().as_os_str())
+        .arg("en")
+        .assert()
+        .success();
+    assert!(file::dirs_contents_are_same(case.join("output"), output.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_po_2() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("po").join("case2");
+    std::fs::copy(
+        case.join("input").join("strings.txt"),
+        input.path().join("strings.txt"),
+    )?;
+    Command::cargo_bin("utas")?
+        .args(["import", "po"])
+        .arg(input.path().as_os_str())
+        .arg(case.join("ru.po").as_os_str())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 translations updated, 1 unchanged, 2 conflicts"))
+        .stdout(predicate::str::contains("ru.po: missing: no key \"missing\""));
+    assert!(file::dirs_contents_are_same(case.join("output"), input.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_csv_1() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("csv").join("case1");
+    Command::cargo_bin("utas")?
+        .args(["export", "csv"])
+        .arg(case.join("input").as_os_str())
+        .arg(output.path().join("strings.csv").as_os_str())
+        .arg("en")
+        .assert()
+        .success();
+    assert!(file::dirs_contents_are_same(case.join("output"), output.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_csv_2() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("csv").join("case2");
+    std::fs::copy(
+        case.join("input").join("strings.txt"),
+        input.path().join("strings.txt"),
+    )?;
+    Command::cargo_bin("utas")?
+        .args(["import", "csv"])
+        .arg(input.path().as_os_str())
+        .arg(case.join("sheet.csv").as_os_str())
+        .arg("--base")
+        .arg(case.join("base.csv").as_os_str())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "3 translations updated, 1 keys created, 6 unchanged, 1 conflicts",
+        ))
+        .stdout(predicate::str::contains("sheet.csv: greeting ru: changed in both"));
+    assert!(file::dirs_contents_are_same(case.join("output"), input.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_android_import_1() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("android_import").join("case1");
+    Command::cargo_bin("utas")?
+        .args(["import", "android"])
+        .arg(case.join("input").as_os_str())
+        .arg(output.path().join("strings.txt").as_os_str())
+        .arg("en")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skipped values-night, it's not a locale"))
+        .stderr(predicate::str::contains("skipped <string-array name=\"days\">"));
+    assert!(file::dirs_contents_are_same(case.join("output"), output.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_ios_import_1() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("ios_import").join("case1");
+    std::fs::copy(
+        case.join("input").join("strings.txt"),
+        input.path().join("strings.txt"),
+    )?;
+    Command::cargo_bin("utas")?
+        .args(["import", "ios"])
+        .arg(case.join("lproj").as_os_str())
+        .arg(input.path().as_os_str())
+        .arg("en")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skipped English.lproj, it's not a locale"))
+        .stdout(predicate::str::contains(
+            "2 translations updated, 1 keys created, 7 unchanged, 1 conflicts",
+        ))
+        .stdout(predicate::str::contains("iOS: greeting: ru differs"));
+    assert!(file::dirs_contents_are_same(case.join("output"), input.path())?);
+    Ok(())
+}
+
+#[test]
+fn case_fmt_1() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("fmt").join("case1");
+    input.copy_from(case.join("input"), &["*.txt"])?;
+    Command::cargo_bin("utas")?
+        .args(["fmt", "--check"])
+        .arg(input.path().as_os_str())
+        .arg("en")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("strings.txt"))
+        .stdout(predicate::str::contains("src1.txt").not());
+    Command::cargo_bin("utas")?
+        .arg("fmt")
+        .arg(input.path().as_os_str())
+        .arg("en")
+        .assert()
+        .success();
+    assert!(file::dirs_contents_are_same(case.join("output"), input.path())?);
+    Command::cargo_bin("utas")?
+        .args(["fmt", "--check"])
+        .arg(input.path().as_os_str())
+        .arg("en")
+        .assert()
+        .success();
+    Ok(())
+}
+
+#[test]
+fn reports_stats() -> Result<(), Box<dyn Error>> {
+    let input = Path::new("tests").join("cases").join("kotlin").join("case1").join("input");
+    Command::cargo_bin("utas")?
+        .arg("stats")
+        .arg(input.as_os_str())
+        .arg("en")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("All sources: 4 keys in en"))
+        .stdout(predicate::str::contains("ru      3 (75%)     1"));
+    Command::cargo_bin("utas")?
+        .args(["stats", "--format", "json"])
+        .arg(input.as_os_str())
+        .arg("en")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"missing_keys\": [\n        \"settings.title\""));
+    Ok(())
+}
+
+#[test]
+fn diffs_sources_by_key() -> Result<(), Box<dyn Error>> {
+    let case = Path::new("tests").join("cases").join("diff").join("case1");
+    Command::cargo_bin("utas")?
+        .arg("diff")
+        .arg(case.join("old").as_os_str())
+        .arg(case.join("new").join("strings.txt").as_os_str())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("added: save\n"))
+        .stdout(predicate::str::contains("renamed: cancel -> button_cancel\n"))
+        .stdout(predicate::str::contains("changed: title\n  en: \"Hello\" -> \"Hello!\"\n"));
+    Command::cargo_bin("utas")?
+        .args(["diff", "--format", "json"])
+        .arg(case.join("old").as_os_str())
+        .arg(case.join("old").as_os_str())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"changed\": []"));
+    Ok(())
+}
+
+#[test]
+fn reports_stale_translations_until_reviewed() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    let output = assert_fs::TempDir::new()?;
+    let source = input.child("strings.txt");
+    source.write_str("[[Main]]\n  [title]\n    en = Hello\n    ru = Привет\n")?;
+    let lock = output.child("utas.lock");
+    let generate = || -> Result<assert_cmd::assert::Assert, Box<dyn Error>> {
+        Ok(Command::cargo_bin("utas")?
+            .arg("android")
+            .arg(input.path().as_os_str())
+            .arg(output.path().as_os_str())
+            .arg("en")
+            .arg("--lock")
+            .arg(lock.path().as_os_str())
+            .assert()
+            .success())
+    };
+    generate()?.stderr(predicate::str::is_empty());
+    lock.assert(predicate::str::contains("\"ru\""));
+
+    source.write_str("[[Main]]\n  [title]\n    en = Hello!\n    ru = Привет\n")?;
+    let warning = "ru of string \"title\" is stale";
+    generate()?.stderr(predicate::str::contains(warning));
+    generate()?.stderr(predicate::str::contains(warning));
+    Command::cargo_bin("utas")?
+        .arg("review")
+        .arg(input.path().as_os_str())
+        .arg("en")
+        .arg(lock.path().as_os_str())
+        .arg("title")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 stale translations marked reviewed"));
+    generate()?.stderr(predicate::str::is_empty());
+    Ok(())
+}
+
+#[test]
+fn rejects_sources_with_the_same_name_only_for_files_per_source() -> Result<(), Box<dyn Error>> {
+    let input = assert_fs::TempDir::new()?;
+    input
+        .child("login/strings.txt")
+        .write_str("[[Login]]\n  [login_title]\n    en = Login\n")?;
+    input
+        .child("profile/strings.txt")
+        .write_str("[[Profile]]\n  [profile_title]\n    en = Profile\n")?;
+    let generate = |platform: &str| -> Result<assert_cmd::assert::Assert, Box<dyn Error>> {
+        let output = assert_fs::TempDir::new()?;
+        Ok(Command::cargo_bin("utas")?
+            .arg(platform)
+            .arg(input.path().as_os_str())
+            .arg(output.path().as_os_str())
+            .arg("en")
+            .arg("--recursive")
+            .assert())
+    };
+    // iOS merges all sources into one table
+    generate("ios")?.success();
+    generate("android")?
+        .failure()
+        .stderr(predicate::str::contains("same name \"strings\""));
+    Ok(())
+}
+
+#[test]
+fn fails_fast_on_first_broken_source() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let input = Path::new("tests").join("cases").join("errors").join("case1").join("input");
+    Command::cargo_bin("utas")?
+        .arg("ios")
+        .arg(input.as_os_str())
+        .arg(output.path().as_os_str())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a_broken.txt"))
+        .stderr(predicate::str::contains("c_broken.txt").not());
+    Ok(())
+}
+
+#[test]
+fn collects_errors_of_all_broken_sources() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let input = Path::new("tests").join("cases").join("errors").join("case1").join("input");
+    Command::cargo_bin("utas")?
+        .arg("android,ios")
+        .arg(input.as_os_str())
+        .arg(output.path().as_os_str())
+        .args(["--errors", "collect"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("2 of 3 sources failed"))
+        .stderr(predicate::str::contains("a_broken.txt"))
+        .stderr(predicate::str::contains("c_broken.txt"));
+    Ok(())
+}
+
+#[test]
+fn generates_in_process_with_library_api() -> Result<(), Box<dyn Error>> {
+    let output = assert_fs::TempDir::new()?;
+    let case = Path::new("tests").join("cases").join("multiplatform").join("case1");
+    let filter = InputFilter::new(false, &[], &[])?;
+    let sources = utas::load_sources(&[case.join("input")], &filter, ErrorMode::FailFast)?;
+    assert_eq!(sources.len(), 1);
+    assert_eq!(sources[0].name, "src1");
+    let keys = &sources[0].file.sections[0].keys;
+    assert_eq!(keys[0].name, "lorem");
+    assert!(matches!(keys[1].localizations[0].value, StringValue::Plural { .. }));
+
+    let options = GenOptions {
+        output_dir: output.to_path_buf(),
+        default_lang: None,
+        file_name: None,
+    };
+    let platforms = ["android".to_string(), "ios".to_string()];
+    let registry = Registry::builtin();
+    utas::validate(&registry, &platforms, &sources)?;
+    utas::generate(&registry, &platforms, &sources, &options)?;
+    assert!(file::dirs_contents_are_same(case.join("output"), output.path())?);
+    Ok(())
+}
+
 fn basic_test_case(
     platform: &str,
     case_rel_path: &str,
     default_lang: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    test_case(platform, "android", case_rel_path, default_lang, &[])
+}
+
+fn test_case(
+    platform: &str,
+    cases_dir: &str,
+    case_rel_path: &str,
+    default_lang: Option<String>,
+    options: &[&str],
 ) -> Result<(), Box<dyn Error>> {
     let mut cmd = Command::cargo_bin("utas")?;
 
@@ -86,22 +617,23 @@ fn basic_test_case(
 
     let input = Path::new("tests")
         .join("cases")
-        .join("android")
+        .join(cases_dir)
         .join(case_rel_path)
         .join("input");
     let output = temp.path();
     let expected = Path::new("tests")
         .join("cases")
-        .join("android")
+        .join(cases_dir)
         .join(case_rel_path)
         .join("output");
 
-    cmd.arg(&platform)
+    cmd.arg(platform)
         .arg(Path::new(&input).as_os_str())
         .arg(output.as_os_str());
-    if default_lang.is_some() {
-        cmd.arg(default_lang.unwrap());
+    if let Some(default_lang) = default_lang {
+        cmd.arg(default_lang);
     }
+    cmd.args(options);
     cmd.assert().success();
     let result = file::compare_dirs_content(expected, output)?;
     match &result {
@@ -115,17 +647,9 @@ fn basic_test_case(
     Ok(())
 }
 
-fn format_result(result: CompareDirsContentResult) -> String {
-    match result {
-        CompareDirsContentResult::Eq => "OK".to_string(),
-        CompareDirsContentResult::Diffs(diffs) => format_diffs(&diffs),
-    }
-}
-
-fn format_diffs(diffs: &Vec<DirDiff>) -> String {
+fn format_diffs(diffs: &[DirDiff]) -> String {
     let mut result = "".to_string();
-    let mut index = 0;
-    for diff in diffs {
+    for (index, diff) in diffs.iter().enumerate() {
         let item = match diff {
             DirDiff::Path { left, right } => format!(
                 "{}. Paths are different: {} and {}\n___________________________________________________________\n\n",
@@ -142,7 +666,6 @@ fn format_diffs(diffs: &Vec<DirDiff>) -> String {
                 )
             }
         };
-        index += 1;
         result.push_str(&item);
     }
     result
@@ -155,7 +678,7 @@ fn format_path(path: &Option<String>) -> String {
     }
 }
 
-fn format_file_diffs(diffs: &Vec<Diff>) -> String {
+fn format_file_diffs(diffs: &[Diff]) -> String {
     let mut result = "".to_string();
     for diff in diffs {
         result.push_str(&format!(